@@ -2,6 +2,7 @@ extern crate bindings_lib;
 extern crate example_lib;
 extern crate http_shared_lib;
 extern crate jotai_logs_lib;
+extern crate jotai_uniffi_lib;
 
 uniffi::setup_scaffolding!();
 
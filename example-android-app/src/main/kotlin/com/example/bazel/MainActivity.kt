@@ -38,9 +38,9 @@ import uniffi.example.printAndAdd
 import uniffi.example_rusqlite.getSaved
 import uniffi.http_shared.setHttpProvider
 import uniffi.jotai_logs.DeleteOldLogsAtom
-import uniffi.jotai_logs.createStore
 import uniffi.jotai_logs.initEffects
 import uniffi.jotai_logs.initLogDb
+import uniffi.jotai_uniffi.createStore
 
 class MainActivity : ComponentActivity() {
   private val store = createStore()
@@ -16,11 +16,11 @@ import java.time.Instant
 import java.time.ZoneId
 import java.time.format.DateTimeFormatter
 import kotlin.math.abs
-import uniffi.jotai_logs.ClosureCallback
 import uniffi.jotai_logs.DeleteLogsAtom
 import uniffi.jotai_logs.Log
 import uniffi.jotai_logs.LogAtom
-import uniffi.jotai_logs.RustJotaiStore
+import uniffi.jotai_logs.LogAtomCallback
+import uniffi.jotai_uniffi.RustJotaiStore
 
 val LocalRustJotaiStore =
     staticCompositionLocalOf<RustJotaiStore> {
@@ -41,9 +41,9 @@ fun LogsView() {
   DisposableEffect(Unit) {
     val cleanup =
         logAtom.sub(
-            object : ClosureCallback {
-              override fun notif() {
-                logItems = logAtom.get()
+            object : LogAtomCallback {
+              override fun onChange(value: List<Log>) {
+                logItems = value
               }
             }
         )
@@ -6,6 +6,7 @@ import drag_example
 import home
 import jotai_example
 import jotai_logs
+import jotai_uniffi
 import rust_jotai_lib
 import rust_logs
 import rust_uniffi_example
@@ -1,6 +1,7 @@
 import DateUtils
 import SwiftUI
 import jotai_logs
+import jotai_uniffi
 import rust_jotai_lib
 
 public struct LogsView: View {
@@ -87,7 +88,7 @@ struct LogsViewInner: View {
 
   @State var cleanup: Cleanup?
   func updateAndTrack() {
-    cleanup = logAtom.sub(func: ClosureWrapper { logItems = logAtom.get() })
+    cleanup = logAtom.sub(callback: LogsCallback { logItems = $0 })
   }
 
   func deleteAll() {
@@ -96,14 +97,14 @@ struct LogsViewInner: View {
 }
 
 // @unchecked: We know that we only use this callback on the UI
-final class ClosureWrapper: ClosureCallback, @unchecked Sendable {
-  let callback: () -> Void
+final class LogsCallback: LogAtomCallback, @unchecked Sendable {
+  let callback: ([Log]) -> Void
 
-  init(_ callback: @escaping () -> Void) {
+  init(_ callback: @escaping ([Log]) -> Void) {
     self.callback = callback
   }
 
-  func notif() {
-    callback()
+  func onChange(value: [Log]) {
+    callback(value)
   }
 }
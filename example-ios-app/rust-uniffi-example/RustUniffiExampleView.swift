@@ -2,6 +2,7 @@ import Log
 import SwiftUI
 import example
 import http_shared_lib
+import jotai_uniffi
 
 public struct RustUniffiExampleView: View {
   @State var ip: String?
@@ -1,3 +1,8 @@
+use jotai::{atom, PrimitiveAtom};
+use jotai_wasm::wasm_atom;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, LazyLock};
+use tsify::Tsify;
 use wasm_bindgen::prelude::*;
 
 #[wasm_bindgen]
@@ -5,3 +10,24 @@ pub fn print_and_add(a: i32, b: i32) -> i32 {
     println!("Hello, World!");
     a + b
 }
+
+#[derive(Tsify, Serialize, Deserialize)]
+#[tsify(into_wasm_abi, from_wasm_abi)]
+pub struct Counter {
+    pub count: i32,
+    pub label: String,
+}
+
+static COUNTER_ATOM: LazyLock<Arc<PrimitiveAtom<i32>>> = LazyLock::new(|| Arc::new(atom(0)));
+
+wasm_atom!(CounterAtom {
+    atom: COUNTER_ATOM,
+    get: Counter => |count| Counter {
+        count: *count,
+        label: format!("count: {count}"),
+    },
+});
+wasm_atom!(SetCounterAtom {
+    atom: COUNTER_ATOM,
+    set: i32,
+});
@@ -1,10 +1,13 @@
 extern crate http_shared_lib;
+extern crate jotai_uniffi_lib;
 extern crate logger;
 
 use http_shared_lib::http::send_request;
 use http_shared_lib::http::HttpMethod;
 use http_shared_lib::http::HttpRequest;
 use http_shared_lib::http::HttpRequestOptions;
+use jotai_uniffi_lib::Cleanup;
+use jotai_uniffi_lib::ClosureCallback;
 use logger::*;
 use std::sync::Arc;
 
@@ -19,22 +22,7 @@ pub fn print_and_add(a: i32, b: i32) -> i32 {
 #[uniffi::export]
 pub fn subber(thing: Box<dyn ClosureCallback>) -> Arc<Cleanup> {
     thing.notif();
-    return Arc::new(Cleanup {});
-}
-
-#[uniffi::export(callback_interface)]
-pub trait ClosureCallback: Send + Sync + 'static {
-    // notify is a reserved word in kotlin 🤦
-    fn notif(&self);
-}
-
-#[derive(uniffi::Object)]
-pub struct Cleanup;
-#[uniffi::export]
-impl Cleanup {
-    fn dispose(&self) {
-        log!("dispose!");
-    }
+    return Arc::new(Cleanup::new(|| log!("dispose!")));
 }
 
 #[uniffi::export]
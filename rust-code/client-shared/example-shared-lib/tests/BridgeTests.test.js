@@ -1,5 +1,25 @@
-const { print_and_add } = require('../example-wasm');
+const {
+  print_and_add,
+  createStore,
+  CounterAtom,
+  SetCounterAtom,
+} = require('../example-wasm');
 
 test("printAndAdd returns 3", async () => {
   expect(print_and_add(1,2)).toBe(3);
 });
+
+test("jotai atoms can be read, set and subscribed to", async () => {
+  const store = createStore();
+  const counter = new CounterAtom(store);
+  const setCounter = new SetCounterAtom(store);
+  const values = [];
+  const dispose = counter.sub((value) => values.push(value));
+
+  setCounter.set(5);
+  expect(counter.get()).toEqual({ count: 5, label: "count: 5" });
+  dispose();
+  setCounter.set(7);
+  expect(values).toEqual([{ count: 5, label: "count: 5" }]);
+  expect(new CounterAtom(createStore()).get().count).toBe(0);
+});
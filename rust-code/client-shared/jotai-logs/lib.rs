@@ -1,77 +1,36 @@
-use jotai::JotaiStore;
+#[cfg(debug_assertions)]
+use jotai::{Atom, StoreEventKind};
+use jotai_uniffi_lib::uniffi_atom;
+use jotai_uniffi_lib::RustJotaiStore;
+use log_atoms::DELETE_LOGS_ATOM;
+use log_atoms::DELETE_OLD_LOGS_ATOM;
 use log_atoms::LOG_ATOM;
 use std::sync::Arc;
+#[cfg(debug_assertions)]
+use std::sync::{Mutex, OnceLock};
 use std::time::SystemTime;
 
 uniffi::setup_scaffolding!();
 
-#[derive(uniffi::Object)]
-struct RustJotaiStore(Arc<JotaiStore>);
-
-#[uniffi::export]
-fn create_store() -> RustJotaiStore {
-    return RustJotaiStore(JotaiStore::new());
-}
-#[derive(uniffi::Object)]
-struct LogAtom {
-    store: Arc<RustJotaiStore>,
-}
-#[uniffi::export]
-impl LogAtom {
-    #[uniffi::constructor]
-    fn new(store: Arc<RustJotaiStore>) -> Arc<Self> {
-        Arc::new(Self { store })
-    }
-    fn get(&self) -> Vec<Log> {
-        let log_atom = LOG_ATOM.clone();
-        self.store
-            .0
-            .clone()
-            .get(&*log_atom)
-            .to_vec()
-            .into_iter()
-            .map(Log::from)
-            .collect()
-    }
-    fn sub(&self, func: Box<dyn ClosureCallback>) -> Cleanup {
-        let log_atom = LOG_ATOM.clone();
-        let store = self.store.0.clone();
-        let dispose = store.sub(log_atom, move |_| func.notif());
-        return Cleanup::new(dispose);
-    }
-}
-
-#[derive(uniffi::Object)]
-struct DeleteLogsAtom {
-    store: Arc<RustJotaiStore>,
-}
-#[uniffi::export]
-impl DeleteLogsAtom {
-    #[uniffi::constructor]
-    fn new(store: Arc<RustJotaiStore>) -> Arc<Self> {
-        Arc::new(Self { store })
-    }
-    fn set(&self) {
-        let _ = log_db::delete_all_logs();
-        log_atoms::invalidate_log(self.store.0.clone());
-    }
-}
-
-#[derive(uniffi::Object)]
-struct DeleteOldLogsAtom {
-    store: Arc<RustJotaiStore>,
-}
-#[uniffi::export]
-impl DeleteOldLogsAtom {
-    #[uniffi::constructor]
-    fn new(store: Arc<RustJotaiStore>) -> Arc<Self> {
-        Arc::new(Self { store })
-    }
-    fn set(&self) {
-        let _ = log_db::delete_old_logs();
-        log_atoms::invalidate_log(self.store.0.clone());
-    }
-}
+uniffi_atom!(LogAtom {
+    atom: LOG_ATOM,
+    get: Vec<Log> => |logs| logs.iter().flatten().cloned().map(Log::from).collect(),
+    callback: LogAtomCallback,
+});
+// The logs stay empty while the db fails, call `retry` on the store to read them again
+uniffi_atom!(LogErrorAtom {
+    atom: LOG_ATOM,
+    get: Option<String> => |logs| logs.as_ref().err().cloned(),
+    callback: LogErrorCallback,
+});
+uniffi_atom!(DeleteLogsAtom {
+    atom: DELETE_LOGS_ATOM,
+    set: (),
+});
+uniffi_atom!(DeleteOldLogsAtom {
+    atom: DELETE_OLD_LOGS_ATOM,
+    set: (),
+});
 
 #[uniffi::export]
 fn init_effects(store: Arc<RustJotaiStore>) {
@@ -79,7 +38,35 @@ fn init_effects(store: Arc<RustJotaiStore>) {
         Box::new(log_db::log_effect()),
         Box::new(log_atoms::invalidate_log_effect(store.0.clone())),
     ]);
+    #[cfg(debug_assertions)]
+    log_store_writes(&store.0);
 }
+
+// Only writes, logging recomputes would recompute the log atom again. Registered once like the
+// logger effects, the remover is kept so it logs for as long as the store lives.
+#[cfg(debug_assertions)]
+fn log_store_writes(store: &Arc<jotai::JotaiStore>) {
+    LOG_STORE_WRITES.get_or_init(|| {
+        Mutex::new(store.add_interceptor(|event| {
+            if !matches!(event.kind, StoreEventKind::Set | StoreEventKind::Dispatch) {
+                return;
+            }
+            // Logging deletes of the logs would write a log straight away
+            let log_atoms = [DELETE_LOGS_ATOM.get_id(), DELETE_OLD_LOGS_ATOM.get_id()];
+            if log_atoms.iter().any(|id| **id == event.atom_id) {
+                return;
+            }
+            let name = event
+                .label
+                .clone()
+                .unwrap_or_else(|| event.atom_id.to_string());
+            logger::log!("{:?} {name} in {:?}", event.kind, event.duration);
+        }))
+    });
+}
+#[cfg(debug_assertions)]
+static LOG_STORE_WRITES: OnceLock<Mutex<Box<dyn FnOnce() + Send>>> = OnceLock::new();
+
 #[uniffi::export]
 pub fn init_log_db(path: &str) {
     log_db::set_db_path(path);
@@ -109,27 +96,3 @@ impl From<log_db::Log> for Log {
         }
     }
 }
-
-#[uniffi::export(callback_interface)]
-pub trait ClosureCallback: Send + Sync {
-    // notify is a reserved word in kotlin 🤦
-    fn notif(&self);
-}
-
-#[derive(uniffi::Object)]
-pub struct Cleanup {
-    callback: Box<dyn Fn() + 'static + Send + Sync>,
-}
-impl Cleanup {
-    fn new(callback: impl Fn() + 'static + Send + Sync) -> Self {
-        Self {
-            callback: Box::new(callback),
-        }
-    }
-}
-#[uniffi::export]
-impl Cleanup {
-    fn dispose(&self) {
-        (self.callback)();
-    }
-}
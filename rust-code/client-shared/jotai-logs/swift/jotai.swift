@@ -1,6 +1,6 @@
 import Foundation
 import SwiftUI
-import jotai_logs
+import jotai_uniffi
 
 @MainActor
 struct RustJotaiStoreKey: EnvironmentKey {
@@ -1,22 +1,33 @@
-use jotai::atom;
-use jotai::select_atom;
+use jotai::dispatch_atom;
+use jotai::result_atom;
+use jotai::DispatchAtom;
 use jotai::JotaiStore;
-use jotai::PrimitiveAtom;
-use jotai::SelectAtom;
+use jotai::ResultAtom;
+use jotai::Setter;
 use log_db::select_log;
 use log_db::Log;
 use std::sync::Arc;
 use std::sync::LazyLock;
 use std::time::SystemTime;
 
-static COUNTER_ATOM: LazyLock<Arc<PrimitiveAtom<usize>>> = LazyLock::new(|| Arc::new(atom(0)));
-pub static LOG_ATOM: LazyLock<Arc<SelectAtom<Vec<Log>>>> = LazyLock::new(|| {
-    Arc::new(select_atom({
-        move |get| {
-            let counter_atom = COUNTER_ATOM.clone();
-            let _ = get.get(counter_atom);
-            select_log().ok().unwrap_or_else(|| vec![])
-        }
+pub static LOG_ATOM: LazyLock<Arc<ResultAtom<Vec<Log>, String>>> = LazyLock::new(|| {
+    Arc::new(
+        result_atom(|_| select_log().map_err(|e| e.to_string()))
+            // Only recomputed when invalidated, so skip comparing every log
+            .with_equals(|_, _| false),
+    )
+});
+
+pub static DELETE_LOGS_ATOM: LazyLock<Arc<DispatchAtom<()>>> = LazyLock::new(|| {
+    Arc::new(dispatch_atom(|setter, _| {
+        let _ = log_db::delete_all_logs();
+        invalidate_log_setter(setter);
+    }))
+});
+pub static DELETE_OLD_LOGS_ATOM: LazyLock<Arc<DispatchAtom<()>>> = LazyLock::new(|| {
+    Arc::new(dispatch_atom(|setter, _| {
+        let _ = log_db::delete_old_logs();
+        invalidate_log_setter(setter);
     }))
 });
 
@@ -29,9 +40,9 @@ pub fn invalidate_log_effect(
 }
 
 pub fn invalidate_log(store: Arc<JotaiStore>) {
-    let counter_atom = COUNTER_ATOM.clone();
-    store.set_primitive(
-        &counter_atom,
-        (*store.clone().get(&*counter_atom) + 1).into(),
-    );
+    store.batch(invalidate_log_setter);
+}
+
+fn invalidate_log_setter(setter: &mut Setter) {
+    setter.refresh(&**LOG_ATOM);
 }
@@ -0,0 +1,541 @@
+use http_shared_lib::http::send_request;
+use http_shared_lib::http::HttpError;
+use http_shared_lib::http::HttpRequest;
+use http_shared_lib::http::HttpResponse;
+use jotai::AsyncAtom;
+use jotai::Atom;
+use jotai::AtomId;
+use jotai::Getter;
+use jotai::JotaiStore;
+use jotai::Loadable;
+use jotai::ReadAtom;
+use jotai::SelectAtom;
+use jotai::Setter;
+use jotai::WriteAtom;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// The last data a query got is kept while it refetches, and after a refetch fails
+#[derive(Debug)]
+pub struct QueryState<T> {
+    pub data: Option<Arc<T>>,
+    pub error: Option<HttpError>,
+    pub is_fetching: bool,
+}
+impl<T> QueryState<T> {
+    /// Fetching without any data to show yet
+    pub fn is_loading(&self) -> bool {
+        self.is_fetching && self.data.is_none()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct QueryOptions {
+    /// How long a response is served from the cache before it's fetched again
+    pub stale_time: Duration,
+    /// Refetches stale data when the query gets its first subscriber
+    pub refetch_on_mount: bool,
+}
+impl Default for QueryOptions {
+    fn default() -> Self {
+        Self {
+            stale_time: Duration::ZERO,
+            refetch_on_mount: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryAction {
+    /// Fetches the current request again, the cached data is shown meanwhile
+    Refetch,
+    /// Marks every cached response stale and fetches the current request again
+    Invalidate,
+}
+
+// `Instant::now` panics on wasm32-unknown-unknown, the JS clock is read there instead
+#[derive(Clone, Copy)]
+struct Timestamp {
+    #[cfg(not(target_arch = "wasm32"))]
+    at: std::time::Instant,
+    #[cfg(target_arch = "wasm32")]
+    millis: f64,
+}
+impl Timestamp {
+    fn now() -> Self {
+        Self {
+            #[cfg(not(target_arch = "wasm32"))]
+            at: std::time::Instant::now(),
+            #[cfg(target_arch = "wasm32")]
+            millis: js_sys::Date::now(),
+        }
+    }
+    fn elapsed(&self) -> Duration {
+        #[cfg(not(target_arch = "wasm32"))]
+        return self.at.elapsed();
+        #[cfg(target_arch = "wasm32")]
+        return Duration::from_secs_f64((js_sys::Date::now() - self.millis).max(0.0) / 1000.0);
+    }
+}
+
+struct CacheEntry<T> {
+    data: Option<Arc<T>>,
+    fetched_at: Option<Timestamp>,
+    fetching: bool,
+    invalidated: bool,
+}
+impl<T> CacheEntry<T> {
+    fn new() -> Self {
+        Self {
+            data: None,
+            fetched_at: None,
+            fetching: false,
+            invalidated: false,
+        }
+    }
+    fn is_fresh(&self, stale_time: Duration) -> bool {
+        !self.invalidated && self.fetched_at.is_some_and(|at| at.elapsed() < stale_time)
+    }
+}
+type Cache<T> = Arc<Mutex<HashMap<HttpRequest, CacheEntry<T>>>>;
+type FetchAtom<T> = AsyncAtom<Arc<T>, HttpError>;
+
+/// Fetches a request built from other atoms through `send_request`, a new request is fetched
+/// whenever they change. Parsed responses are cached per request until they're stale and another
+/// request is fetched, write a `QueryAction` to refetch or invalidate them.
+pub struct QueryAtom<T> {
+    state_atom: SelectAtom<QueryState<T>>,
+    request_atom: Arc<SelectAtom<HttpRequest>>,
+    fetch_atom: Arc<FetchAtom<T>>,
+    cache: Cache<T>,
+    options: QueryOptions,
+}
+impl<T: Send + Sync + 'static> PartialEq for QueryAtom<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.get_id() == other.get_id()
+    }
+}
+impl<T: Send + Sync + 'static> Eq for QueryAtom<T> {}
+impl<T: Send + Sync + 'static> Hash for QueryAtom<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.get_id().hash(state);
+    }
+}
+impl<T: Send + Sync + 'static> QueryAtom<T> {
+    pub fn new<R, P>(request: R, parse: P) -> Self
+    where
+        R: Fn(&mut Getter) -> HttpRequest + 'static + Send + Sync,
+        P: Fn(&HttpResponse) -> Result<T, HttpError> + 'static + Send + Sync,
+    {
+        Self::new_with_options(request, parse, QueryOptions::default())
+    }
+
+    pub fn new_with_options<R, P>(request: R, parse: P, options: QueryOptions) -> Self
+    where
+        R: Fn(&mut Getter) -> HttpRequest + 'static + Send + Sync,
+        P: Fn(&HttpResponse) -> Result<T, HttpError> + 'static + Send + Sync,
+    {
+        let cache: Cache<T> = Arc::new(Mutex::new(HashMap::new()));
+        let request_atom = Arc::new(SelectAtom::new(request));
+        let fetch_atom = Arc::new(fetch_atom(
+            request_atom.clone(),
+            Arc::new(parse),
+            cache.clone(),
+            options.stale_time,
+        ));
+        let state_atom = SelectAtom::new_with_equals(
+            {
+                let (request_atom, fetch_atom) = (request_atom.clone(), fetch_atom.clone());
+                let cache = cache.clone();
+                move |getter| {
+                    let loadable = getter.get(fetch_atom.clone());
+                    let request = getter.get(request_atom.clone());
+                    let cached = cache
+                        .lock()
+                        .unwrap()
+                        .get(&*request)
+                        .and_then(|entry| entry.data.clone());
+                    match &*loadable {
+                        Loadable::Loading => QueryState {
+                            data: cached,
+                            error: None,
+                            is_fetching: true,
+                        },
+                        Loadable::Value(data) => QueryState {
+                            data: Some(data.clone()),
+                            error: None,
+                            is_fetching: false,
+                        },
+                        Loadable::Error(error) => QueryState {
+                            data: cached,
+                            error: Some(error.clone()),
+                            is_fetching: false,
+                        },
+                    }
+                }
+            },
+            // Cached data is shared, so comparing pointers is enough. Errors always notify.
+            Arc::new(|a: &QueryState<T>, b: &QueryState<T>| {
+                a.is_fetching == b.is_fetching
+                    && same_data(&a.data, &b.data)
+                    && a.error.is_none()
+                    && b.error.is_none()
+            }),
+        );
+        Self {
+            state_atom,
+            request_atom,
+            fetch_atom,
+            cache,
+            options,
+        }
+    }
+}
+impl<T: Send + Sync + 'static> Atom for QueryAtom<T> {
+    fn get_id(&self) -> Arc<AtomId> {
+        self.state_atom.get_id()
+    }
+    fn on_mount(&self, store: &Arc<JotaiStore>) -> Option<Box<dyn FnOnce() + Send>> {
+        if !self.options.refetch_on_mount {
+            return None;
+        }
+        let request = store.clone().get(&*self.request_atom);
+        let stale = self
+            .cache
+            .lock()
+            .unwrap()
+            .get(&*request)
+            .is_some_and(|entry| !entry.fetching && !entry.is_fresh(self.options.stale_time));
+        if stale {
+            store.clone().refresh(&*self.fetch_atom);
+        }
+        None
+    }
+}
+impl<T: Send + Sync + 'static> ReadAtom<QueryState<T>> for QueryAtom<T> {
+    fn get_read(&self) -> &Box<dyn Fn(&mut Getter) -> QueryState<T> + Send + Sync> {
+        self.state_atom.get_read()
+    }
+    fn is_equal(&self, a: &QueryState<T>, b: &QueryState<T>) -> bool {
+        self.state_atom.is_equal(a, b)
+    }
+    fn is_error(&self, value: &QueryState<T>) -> bool {
+        value.error.is_some()
+    }
+}
+impl<T: Send + Sync + 'static> WriteAtom<QueryAction> for QueryAtom<T> {
+    fn write(&self, setter: &mut Setter, action: Arc<QueryAction>) {
+        let request = setter.get(self.request_atom.clone());
+        {
+            let mut cache = self.cache.lock().unwrap();
+            match *action {
+                QueryAction::Refetch => {
+                    if let Some(entry) = cache.get_mut(&*request) {
+                        entry.invalidated = true;
+                    }
+                }
+                QueryAction::Invalidate => {
+                    cache
+                        .values_mut()
+                        .for_each(|entry| entry.invalidated = true);
+                }
+            }
+        }
+        setter.refresh(&*self.fetch_atom);
+    }
+}
+
+pub fn query_atom<T, R, P>(request: R, parse: P) -> QueryAtom<T>
+where
+    T: Send + Sync + 'static,
+    R: Fn(&mut Getter) -> HttpRequest + 'static + Send + Sync,
+    P: Fn(&HttpResponse) -> Result<T, HttpError> + 'static + Send + Sync,
+{
+    QueryAtom::new(request, parse)
+}
+
+fn fetch_atom<T, P>(
+    request_atom: Arc<SelectAtom<HttpRequest>>,
+    parse: Arc<P>,
+    cache: Cache<T>,
+    stale_time: Duration,
+) -> FetchAtom<T>
+where
+    T: Send + Sync + 'static,
+    P: Fn(&HttpResponse) -> Result<T, HttpError> + 'static + Send + Sync,
+{
+    AsyncAtom::new_with_equals(
+        move |getter: Getter| {
+            let request = getter.get(request_atom.clone());
+            let cached = {
+                let mut cache = cache.lock().unwrap();
+                // Responses of other requests are only kept while they're fresh
+                cache.retain(|key, entry| {
+                    key == &*request || entry.fetching || entry.is_fresh(stale_time)
+                });
+                let entry = cache
+                    .entry((*request).clone())
+                    .or_insert_with(CacheEntry::new);
+                if entry.is_fresh(stale_time) {
+                    entry.data.clone()
+                } else {
+                    entry.fetching = true;
+                    None
+                }
+            };
+            let (cache, parse) = (cache.clone(), parse.clone());
+            async move {
+                if let Some(data) = cached {
+                    return Ok(data);
+                }
+                let result = match &*send_request((*request).clone()).await {
+                    Ok(response) => parse(response).map(Arc::new),
+                    Err(error) => Err(error.clone()),
+                };
+                let mut cache = cache.lock().unwrap();
+                let entry = cache
+                    .entry((*request).clone())
+                    .or_insert_with(CacheEntry::new);
+                entry.fetching = false;
+                match &result {
+                    Ok(data) => {
+                        entry.data = Some(data.clone());
+                        entry.fetched_at = Some(Timestamp::now());
+                        entry.invalidated = false;
+                    }
+                    // Keeps the previous data, but the next read fetches again
+                    Err(_) => entry.invalidated = true,
+                }
+                result
+            }
+        },
+        Arc::new(|a, b| match (a, b) {
+            (Loadable::Loading, Loadable::Loading) => true,
+            (Loadable::Value(a), Loadable::Value(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }),
+    )
+}
+
+fn same_data<T>(a: &Option<Arc<T>>, b: &Option<Arc<T>>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http_shared_lib::http::{register_http_provider, HttpMethod, HttpProvider};
+    use http_shared_lib::http::{HttpRequestOptions, GLOBAL_HTTP_PROVIDER};
+    use jotai::atom;
+    use std::collections::BTreeMap;
+    use std::future::poll_fn;
+    use std::sync::LazyLock;
+    use std::task::{Poll, Waker};
+
+    use super::*;
+
+    // Answers "<url> <n>" to the nth request of a url, urls starting with /missing fail
+    #[derive(Default)]
+    struct MockHttp {
+        counts: Mutex<HashMap<String, usize>>,
+        paused: Mutex<Option<String>>,
+        waiting: Mutex<Vec<Waker>>,
+    }
+    impl MockHttp {
+        fn pause(&self, url: &str) {
+            *self.paused.lock().unwrap() = Some(url.to_string());
+        }
+        fn resume(&self) {
+            *self.paused.lock().unwrap() = None;
+            let waiting: Vec<_> = self.waiting.lock().unwrap().drain(..).collect();
+            waiting.into_iter().for_each(Waker::wake);
+        }
+        fn count(&self, url: &str) -> usize {
+            *self.counts.lock().unwrap().get(url).unwrap_or(&0)
+        }
+    }
+    struct MockProvider;
+    #[async_trait::async_trait]
+    impl HttpProvider for MockProvider {
+        async fn send_request(&self, request: HttpRequest) -> Result<HttpResponse, HttpError> {
+            poll_fn(|cx| {
+                if MOCK.paused.lock().unwrap().as_ref() != Some(&request.url) {
+                    return Poll::Ready(());
+                }
+                MOCK.waiting.lock().unwrap().push(cx.waker().clone());
+                Poll::Pending
+            })
+            .await;
+            if request.url.starts_with("/missing") {
+                return Err(HttpError::NetworkError("offline".to_string()));
+            }
+            let mut counts = MOCK.counts.lock().unwrap();
+            let count = counts.entry(request.url.clone()).or_default();
+            *count += 1;
+            Ok(HttpResponse {
+                status_code: 200,
+                headers: BTreeMap::new(),
+                body: format!("{} {}", request.url, count).into_bytes(),
+            })
+        }
+    }
+    static MOCK: LazyLock<MockHttp> = LazyLock::new(|| {
+        register_http_provider(Box::new(MockProvider));
+        assert!(GLOBAL_HTTP_PROVIDER.get().is_some());
+        MockHttp::default()
+    });
+
+    fn get(url: &str) -> HttpRequest {
+        HttpRequest {
+            url: url.to_string(),
+            method: HttpMethod::Get,
+            headers: None,
+            body: None,
+            options: HttpRequestOptions::SKIP_LOG,
+        }
+    }
+    fn text(response: &HttpResponse) -> Result<String, HttpError> {
+        Ok(String::from_utf8_lossy(&response.body).to_string())
+    }
+    fn data(state: &QueryState<String>) -> Option<String> {
+        state.data.as_deref().cloned()
+    }
+
+    #[test]
+    fn test_query_atom() {
+        LazyLock::force(&MOCK);
+        let store = JotaiStore::new();
+        let page_atom = Arc::new(atom(1));
+        let items_atom = Arc::new(QueryAtom::new_with_options(
+            {
+                let page_atom = page_atom.clone();
+                move |getter| get(&format!("/items/{}", getter.get(page_atom.clone())))
+            },
+            text,
+            QueryOptions {
+                stale_time: Duration::from_secs(3600),
+                refetch_on_mount: true,
+            },
+        ));
+        assert_eq!(
+            data(&store.clone().get(&*items_atom)).unwrap(),
+            "/items/1 1"
+        );
+
+        // Cached per request while it's fresh
+        store.clone().set_primitive(&page_atom, Arc::new(2));
+        assert_eq!(
+            data(&store.clone().get(&*items_atom)).unwrap(),
+            "/items/2 1"
+        );
+        store.clone().set_primitive(&page_atom, Arc::new(1));
+        assert_eq!(
+            data(&store.clone().get(&*items_atom)).unwrap(),
+            "/items/1 1"
+        );
+        assert_eq!(MOCK.count("/items/1"), 1);
+
+        // Fresh data isn't refetched on mount
+        let seen = Arc::new(Mutex::new(vec![]));
+        let _dispose = store.clone().sub_value(items_atom.clone(), {
+            let seen = seen.clone();
+            move |state, _| seen.lock().unwrap().push((data(&state), state.is_fetching))
+        });
+        assert_eq!(MOCK.count("/items/1"), 1);
+
+        // The previous data is shown while refetching
+        MOCK.pause("/items/1");
+        store
+            .clone()
+            .set(&*items_atom, Arc::new(QueryAction::Refetch));
+        MOCK.resume();
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![
+                (Some("/items/1 1".to_string()), true),
+                (Some("/items/1 2".to_string()), false),
+            ]
+        );
+
+        // Invalidating refetches the current request, the others on their next read
+        store
+            .clone()
+            .set(&*items_atom, Arc::new(QueryAction::Invalidate));
+        assert_eq!(
+            data(&store.clone().get(&*items_atom)).unwrap(),
+            "/items/1 3"
+        );
+        store.clone().set_primitive(&page_atom, Arc::new(2));
+        assert_eq!(
+            data(&store.clone().get(&*items_atom)).unwrap(),
+            "/items/2 2"
+        );
+    }
+
+    #[test]
+    fn test_query_atom_evicts_stale() {
+        LazyLock::force(&MOCK);
+        let store = JotaiStore::new();
+        let term_atom = Arc::new(atom("a"));
+        let search_atom = Arc::new(query_atom(
+            {
+                let term_atom = term_atom.clone();
+                move |getter| get(&format!("/search/{}", getter.get(term_atom.clone())))
+            },
+            text,
+        ));
+        for term in ["a", "b", "c"] {
+            store.clone().set_primitive(&term_atom, Arc::new(term));
+            assert_eq!(
+                data(&store.clone().get(&*search_atom)).unwrap(),
+                format!("/search/{term} 1")
+            );
+        }
+        let cache = search_atom.cache.lock().unwrap();
+        assert_eq!(cache.len(), 1);
+        assert!(cache.contains_key(&get("/search/c")));
+    }
+
+    #[test]
+    fn test_query_atom_refetch_on_mount() {
+        LazyLock::force(&MOCK);
+        let store = JotaiStore::new();
+        let user_atom = Arc::new(query_atom(|_| get("/user"), text));
+        let settings_atom = Arc::new(QueryAtom::new_with_options(
+            |_| get("/settings"),
+            text,
+            QueryOptions {
+                refetch_on_mount: false,
+                ..QueryOptions::default()
+            },
+        ));
+        assert_eq!(data(&store.clone().get(&*user_atom)).unwrap(), "/user 1");
+        assert_eq!(
+            data(&store.clone().get(&*settings_atom)).unwrap(),
+            "/settings 1"
+        );
+
+        let _dispose_user = store.clone().sub(user_atom.clone(), |_| {});
+        let _dispose_settings = store.clone().sub(settings_atom.clone(), |_| {});
+        assert_eq!(data(&store.clone().get(&*user_atom)).unwrap(), "/user 2");
+        assert_eq!(
+            data(&store.clone().get(&*settings_atom)).unwrap(),
+            "/settings 1"
+        );
+    }
+
+    #[test]
+    fn test_query_atom_error() {
+        LazyLock::force(&MOCK);
+        let store = JotaiStore::new();
+        let missing_atom = Arc::new(query_atom(|_| get("/missing"), text));
+        let state = store.clone().get(&*missing_atom);
+        assert!(state.data.is_none() && !state.is_fetching);
+        assert!(matches!(state.error, Some(HttpError::NetworkError(_))));
+    }
+}
@@ -0,0 +1,326 @@
+use std::future::Future;
+use std::hash::{Hash, Hasher};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Wake, Waker};
+
+use crate::atom_base::*;
+use crate::getter_setter::Getter;
+
+type Read<T, E> = Box<dyn Fn(&mut Getter) -> Loadable<T, E> + Send + Sync>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Loadable<T, E> {
+    Loading,
+    Value(T),
+    Error(E),
+}
+impl<T, E> Loadable<T, E> {
+    pub fn is_loading(&self) -> bool {
+        matches!(self, Loadable::Loading)
+    }
+    pub fn value(&self) -> Option<&T> {
+        match self {
+            Loadable::Value(v) => Some(v),
+            _ => None,
+        }
+    }
+    pub fn error(&self) -> Option<&E> {
+        match self {
+            Loadable::Error(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+pub struct AsyncAtom<T, E> {
+    id: Arc<AtomId>,
+    read: Read<T, E>,
+    equals: Equals<Loadable<T, E>>,
+}
+impl<T, E> PartialEq for AsyncAtom<T, E> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+impl<T, E> Eq for AsyncAtom<T, E> {}
+impl<T, E> Hash for AsyncAtom<T, E> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+impl<T: PartialEq + Send + Sync + 'static, E: PartialEq + Send + Sync + 'static> AsyncAtom<T, E> {
+    pub fn new<F, Fut>(f: F) -> Self
+    where
+        F: Fn(Getter) -> Fut + 'static + Send + Sync,
+        Fut: Future<Output = Result<T, E>> + 'static + Send,
+    {
+        Self::new_with_equals(f, default_equals())
+    }
+}
+impl<T: Send + Sync + 'static, E: Send + Sync + 'static> AsyncAtom<T, E> {
+    /// For values without `PartialEq`, or where comparing them is too expensive
+    pub fn new_with_equals<F, Fut>(f: F, equals: Equals<Loadable<T, E>>) -> Self
+    where
+        F: Fn(Getter) -> Fut + 'static + Send + Sync,
+        Fut: Future<Output = Result<T, E>> + 'static + Send,
+    {
+        Self {
+            id: Arc::new(AtomId::new()),
+            read: Box::new(move |getter| {
+                // The first poll happens inline so that futures which are already ready don't
+                // flash a Loading state. Anything resolved later is pushed back into the store.
+                let phase = Arc::new(Mutex::new(Phase::Initial));
+                let future = f(getter.clone());
+                let store = Arc::downgrade(&getter.store);
+                let atom_id = Arc::downgrade(&getter.atom_id);
+                let getter_id = getter.id;
+                let task = Task::new({
+                    let phase = phase.clone();
+                    async move {
+                        let loadable = match future.await {
+                            Ok(v) => Loadable::Value(v),
+                            Err(e) => Loadable::Error(e),
+                        };
+                        let loadable = {
+                            let mut phase = phase.lock().unwrap();
+                            match *phase {
+                                Phase::Initial => {
+                                    *phase = Phase::Ready(loadable);
+                                    return;
+                                }
+                                _ => loadable,
+                            }
+                        };
+                        if let (Some(store), Some(atom_id)) = (store.upgrade(), atom_id.upgrade()) {
+                            let failed = loadable.error().is_some();
+                            store.resolve_async(atom_id, getter_id, Arc::new(loadable), failed);
+                        }
+                    }
+                });
+                task.wake_by_ref();
+                let mut phase = phase.lock().unwrap();
+                match std::mem::replace(&mut *phase, Phase::Detached) {
+                    Phase::Ready(loadable) => loadable,
+                    _ => Loadable::Loading,
+                }
+            }),
+            equals,
+        }
+    }
+
+    /// Replaces the equality used to decide whether a new result notifies dependents
+    pub fn with_equals<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&Loadable<T, E>, &Loadable<T, E>) -> bool + 'static + Send + Sync,
+    {
+        self.equals = Arc::new(f);
+        self
+    }
+}
+impl<T, E> Atom for AsyncAtom<T, E> {
+    fn get_id(&self) -> Arc<AtomId> {
+        self.id.clone()
+    }
+}
+impl<T, E> ReadAtom<Loadable<T, E>> for AsyncAtom<T, E> {
+    fn get_read(&self) -> &Box<dyn Fn(&mut Getter) -> Loadable<T, E> + Send + Sync> {
+        &self.read
+    }
+    fn is_equal(&self, a: &Loadable<T, E>, b: &Loadable<T, E>) -> bool {
+        (self.equals)(a, b)
+    }
+    fn is_error(&self, value: &Loadable<T, E>) -> bool {
+        value.error().is_some()
+    }
+}
+
+enum Phase<L> {
+    Initial,
+    Ready(L),
+    Detached,
+}
+
+// Minimal self-driving task: it's polled on whichever thread wakes it, so no runtime is needed
+struct Task {
+    future: Mutex<Option<Pin<Box<dyn Future<Output = ()> + Send>>>>,
+    notified: AtomicBool,
+}
+impl Task {
+    fn new(future: impl Future<Output = ()> + 'static + Send) -> Arc<Self> {
+        Arc::new(Self {
+            future: Mutex::new(Some(Box::pin(future))),
+            notified: AtomicBool::new(false),
+        })
+    }
+}
+impl Wake for Task {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.notified.store(true, Ordering::SeqCst);
+        // If someone else is polling, they'll see notified and poll again
+        while let Ok(mut slot) = self.future.try_lock() {
+            if !self.notified.swap(false, Ordering::SeqCst) {
+                return;
+            }
+            let Some(future) = slot.as_mut() else {
+                return;
+            };
+            let waker = Waker::from(self.clone());
+            if future
+                .as_mut()
+                .poll(&mut Context::from_waker(&waker))
+                .is_ready()
+            {
+                *slot = None;
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::poll_fn;
+    use std::task::Poll;
+
+    use super::*;
+    use crate::*;
+
+    #[derive(Default)]
+    struct Gate {
+        open: bool,
+        waker: Option<Waker>,
+    }
+    fn gate(gates: &Arc<Mutex<Vec<Arc<Mutex<Gate>>>>>) -> impl Future<Output = ()> + use<> {
+        let gate = Arc::new(Mutex::new(Gate::default()));
+        gates.lock().unwrap().push(gate.clone());
+        poll_fn(move |cx| {
+            let mut gate = gate.lock().unwrap();
+            if gate.open {
+                return Poll::Ready(());
+            }
+            gate.waker = Some(cx.waker().clone());
+            Poll::Pending
+        })
+    }
+    fn open_all(gates: &Arc<Mutex<Vec<Arc<Mutex<Gate>>>>>) {
+        let gates: Vec<_> = gates.lock().unwrap().drain(..).collect();
+        for gate in gates {
+            let waker = {
+                let mut gate = gate.lock().unwrap();
+                gate.open = true;
+                gate.waker.take()
+            };
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        }
+    }
+
+    #[test]
+    fn test_ready_async_atom() {
+        let store = JotaiStore::new();
+        let value_atom = Arc::new(atom(10));
+        let double_atom = async_atom({
+            let value_atom = value_atom.clone();
+            move |getter| {
+                let value_atom = value_atom.clone();
+                async move { Ok::<_, ()>(*getter.get(value_atom) * 2) }
+            }
+        });
+        assert_eq!(*store.clone().get(&double_atom), Loadable::Value(20));
+    }
+
+    #[test]
+    fn test_pending_async_atom() {
+        let store = JotaiStore::new();
+        let gates = Arc::new(Mutex::new(vec![]));
+        let value_atom = Arc::new(atom(10));
+        let double_atom = Arc::new(async_atom({
+            let value_atom = value_atom.clone();
+            let gates = gates.clone();
+            move |getter| {
+                let value_atom = value_atom.clone();
+                let wait = gate(&gates);
+                async move {
+                    wait.await;
+                    // tracked after the await point
+                    Ok::<_, ()>(*getter.get(value_atom) * 2)
+                }
+            }
+        }));
+        let select_counter = Arc::new(Mutex::new(0));
+        let is_loaded_atom = Arc::new(select_atom({
+            let double_atom = double_atom.clone();
+            let counter = select_counter.clone();
+            move |getter| {
+                *counter.lock().unwrap() += 1;
+                !getter.get(double_atom.clone()).is_loading()
+            }
+        }));
+        let sub_counter = Arc::new(Mutex::new(0));
+        let _dispose = store.clone().sub(double_atom.clone(), {
+            let counter = sub_counter.clone();
+            move |_| *counter.lock().unwrap() += 1
+        });
+        assert_eq!(*store.clone().get(&*double_atom), Loadable::Loading);
+        assert!(!*store.clone().get(&*is_loaded_atom));
+        assert_eq!(*select_counter.lock().unwrap(), 1);
+
+        open_all(&gates);
+        assert_eq!(*sub_counter.lock().unwrap(), 1);
+        assert_eq!(*store.clone().get(&*double_atom), Loadable::Value(20));
+        assert!(*store.clone().get(&*is_loaded_atom));
+        assert_eq!(*select_counter.lock().unwrap(), 2);
+
+        // dependency read after the await is tracked
+        store.clone().set_primitive(&value_atom, Arc::new(11));
+        assert_eq!(*sub_counter.lock().unwrap(), 2);
+        assert_eq!(*store.clone().get(&*double_atom), Loadable::Loading);
+        open_all(&gates);
+        assert_eq!(*sub_counter.lock().unwrap(), 3);
+        assert_eq!(*store.clone().get(&*double_atom), Loadable::Value(22));
+    }
+
+    #[test]
+    fn test_outdated_async_atom() {
+        let store = JotaiStore::new();
+        let gates = Arc::new(Mutex::new(vec![]));
+        let value_atom = Arc::new(atom(10));
+        let value_async_atom = async_atom({
+            let value_atom = value_atom.clone();
+            let gates = gates.clone();
+            move |getter| {
+                let value = *getter.get(value_atom.clone());
+                let wait = gate(&gates);
+                async move {
+                    wait.await;
+                    Ok::<_, ()>(value)
+                }
+            }
+        });
+        assert_eq!(*store.clone().get(&value_async_atom), Loadable::Loading);
+        let first_gate = gates.lock().unwrap().pop().unwrap();
+        store.clone().set_primitive(&value_atom, Arc::new(11));
+        assert_eq!(*store.clone().get(&value_async_atom), Loadable::Loading);
+
+        // Resolving the first read must not overwrite the newer one
+        gates.lock().unwrap().push(first_gate);
+        open_all(&gates);
+        assert_eq!(*store.clone().get(&value_async_atom), Loadable::Value(11));
+    }
+
+    #[test]
+    fn test_error_async_atom() {
+        let store = JotaiStore::new();
+        let failing_atom = async_atom(|_| async { Err::<u32, _>("failed".to_string()) });
+        assert_eq!(
+            *store.clone().get(&failing_atom),
+            Loadable::Error("failed".to_string())
+        );
+    }
+}
@@ -1,24 +1,73 @@
+use std::fmt;
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, LazyLock, Mutex, Weak};
 
-use crate::getter_setter::Getter;
+use weak_table::WeakKeyHashMap;
+
+use crate::getter_setter::{Getter, Setter};
+use crate::jotai_store::JotaiStore;
 
 // Global, thread-safe counter
 static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
 fn new_id() -> usize {
     NEXT_ID.fetch_add(1, Ordering::Relaxed)
 }
+// Debug labels, kept outside the atoms so every atom type can have one
+static LABELS: LazyLock<Mutex<WeakKeyHashMap<Weak<AtomId>, String>>> =
+    LazyLock::new(|| Mutex::new(WeakKeyHashMap::new()));
 
 pub trait Atom {
     fn get_id(&self) -> Arc<AtomId>;
+    /// Called when the atom gets its first subscriber, the returned cleanup runs on unmount
+    fn on_mount(&self, _store: &Arc<JotaiStore>) -> Option<Box<dyn FnOnce() + Send>> {
+        None
+    }
+    /// Primitive atoms hold state, everything else can be derived from them
+    fn is_primitive(&self) -> bool {
+        false
+    }
+    /// Names the atom in store inspection and graph exports
+    fn with_label(self, label: &str) -> Self
+    where
+        Self: Sized,
+    {
+        LABELS
+            .lock()
+            .unwrap()
+            .insert(self.get_id(), label.to_string());
+        self
+    }
+}
+/// Decides whether a new value is the same as the old one, in which case dependents and
+/// subscribers aren't notified
+pub type Equals<T> = Arc<dyn Fn(&T, &T) -> bool + Send + Sync>;
+pub(crate) fn default_equals<T: PartialEq>() -> Equals<T> {
+    Arc::new(|a, b| a == b)
 }
-pub trait ReadAtom<T>: Atom {
+
+pub trait ReadAtom<T>: Atom + Send + Sync {
     fn get_read(&self) -> &Box<dyn Fn(&mut Getter) -> T + Send + Sync>;
+    fn is_equal(&self, a: &T, b: &T) -> bool;
+    /// Failed values are cached like any other, `JotaiStore::retry` recomputes them
+    fn is_error(&self, _value: &T) -> bool {
+        false
+    }
+}
+pub trait WriteAtom<Arg>: Atom {
+    fn write(&self, setter: &mut Setter, arg: Arc<Arg>);
 }
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct AtomId(usize);
 impl AtomId {
     pub(crate) fn new() -> Self {
         Self(new_id())
     }
+    pub fn label(&self) -> Option<String> {
+        LABELS.lock().unwrap().get(self).cloned()
+    }
+}
+impl fmt::Display for AtomId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
 }
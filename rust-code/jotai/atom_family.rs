@@ -0,0 +1,79 @@
+use std::hash::Hash;
+use std::sync::{Arc, Mutex, Weak};
+
+use weak_table::WeakValueHashMap;
+
+pub struct AtomFamily<K, A> {
+    create: Box<dyn Fn(&K) -> A + Send + Sync>,
+    // Atoms are only held weakly, once every user drops the atom the entry goes too
+    atoms: Mutex<WeakValueHashMap<K, Weak<A>>>,
+}
+impl<K: Hash + Eq, A> AtomFamily<K, A> {
+    pub fn new<F>(create: F) -> Self
+    where
+        F: Fn(&K) -> A + 'static + Send + Sync,
+    {
+        Self {
+            create: Box::new(create),
+            atoms: Mutex::new(WeakValueHashMap::new()),
+        }
+    }
+
+    pub fn get(&self, key: K) -> Arc<A> {
+        let mut atoms = self.atoms.lock().unwrap();
+        if let Some(atom) = atoms.get(&key) {
+            return atom;
+        }
+        let atom = Arc::new((self.create)(&key));
+        atoms.insert(key, atom.clone());
+        atom
+    }
+
+    /// Forgets the atom for this key, the next get creates a fresh atom.
+    /// Existing holders of the old atom keep their (now detached) copy.
+    pub fn remove(&self, key: &K) {
+        self.atoms.lock().unwrap().remove(key);
+    }
+
+    pub fn contains(&self, key: &K) -> bool {
+        self.atoms.lock().unwrap().contains_key(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn test_atom_family() {
+        let store = JotaiStore::new();
+        let family = atom_family(|id: &u32| atom(*id * 10));
+        let atom_1 = family.get(1);
+        let atom_2 = family.get(2);
+        assert!(Arc::ptr_eq(&atom_1, &family.get(1)));
+        assert!(!Arc::ptr_eq(&atom_1, &atom_2));
+        assert_eq!(*store.clone().get(&*atom_1), 10);
+        assert_eq!(*store.clone().get(&*atom_2), 20);
+
+        store.clone().set_primitive(&family.get(1), Arc::new(11));
+        assert_eq!(*store.clone().get(&*atom_1), 11);
+    }
+
+    #[test]
+    fn test_atom_family_drop() {
+        let family = atom_family(|id: &u32| atom(*id));
+        let atom_id = family.get(1).get_id();
+        assert!(!family.contains(&1));
+        assert_ne!(family.get(1).get_id(), atom_id);
+    }
+
+    #[test]
+    fn test_atom_family_remove() {
+        let family = atom_family(|id: &u32| atom(*id));
+        let atom_1 = family.get(1);
+        assert!(family.contains(&1));
+        family.remove(&1);
+        assert!(!family.contains(&1));
+        assert!(!Arc::ptr_eq(&atom_1, &family.get(1)));
+    }
+}
@@ -1,4 +1,6 @@
+use std::future::Future;
 use std::hash::{Hash, Hasher};
+use std::pin::Pin;
 use std::sync::Arc;
 
 use crate::atom_base::*;
@@ -6,7 +8,7 @@ use crate::getter_setter::Setter;
 
 pub struct DispatchAtom<Arg> {
     id: Arc<AtomId>,
-    pub(crate) dispatch: Box<dyn Fn(&mut Setter, Arc<Arg>)>,
+    pub(crate) dispatch: Box<dyn Fn(&mut Setter, Arc<Arg>) + Send + Sync>,
 }
 impl<Arg> PartialEq for DispatchAtom<Arg> {
     fn eq(&self, other: &Self) -> bool {
@@ -22,7 +24,7 @@ impl<Arg> Hash for DispatchAtom<Arg> {
 impl<Arg> DispatchAtom<Arg> {
     pub fn new<F>(setter: F) -> Self
     where
-        F: Fn(&mut Setter, Arc<Arg>) + 'static,
+        F: Fn(&mut Setter, Arc<Arg>) + 'static + Send + Sync,
     {
         Self {
             id: Arc::new(AtomId::new()),
@@ -35,10 +37,15 @@ impl<Arg> Atom for DispatchAtom<Arg> {
         self.id.clone()
     }
 }
+impl<Arg> WriteAtom<Arg> for DispatchAtom<Arg> {
+    fn write(&self, setter: &mut Setter, arg: Arc<Arg>) {
+        (self.dispatch)(setter, arg)
+    }
+}
 
 pub struct DispatchWithReturnAtom<Arg, Return> {
     id: Arc<AtomId>,
-    pub(crate) dispatch: Box<dyn Fn(&mut Setter, Arc<Arg>) -> Return>,
+    pub(crate) dispatch: Box<dyn Fn(&mut Setter, Arc<Arg>) -> Return + Send + Sync>,
 }
 impl<Arg, Return> PartialEq for DispatchWithReturnAtom<Arg, Return> {
     fn eq(&self, other: &Self) -> bool {
@@ -54,7 +61,7 @@ impl<Arg, Return> Hash for DispatchWithReturnAtom<Arg, Return> {
 impl<Arg, Return> DispatchWithReturnAtom<Arg, Return> {
     pub fn new<F>(setter: F) -> Self
     where
-        F: Fn(&mut Setter, Arc<Arg>) -> Return + 'static,
+        F: Fn(&mut Setter, Arc<Arg>) -> Return + 'static + Send + Sync,
     {
         Self {
             id: Arc::new(AtomId::new()),
@@ -67,3 +74,42 @@ impl<Arg, Return> Atom for DispatchWithReturnAtom<Arg, Return> {
         self.id.clone()
     }
 }
+
+type AsyncDispatch<Arg> =
+    Box<dyn Fn(Setter, Arc<Arg>) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync>;
+
+/// Like `DispatchAtom`, but the write can await between setter calls. The store isn't locked
+/// while it awaits, so every call is applied on its own and other writes can land in between. Use
+/// `Setter::batch` for reads and writes that have to happen together.
+pub struct AsyncDispatchAtom<Arg> {
+    id: Arc<AtomId>,
+    pub(crate) dispatch: AsyncDispatch<Arg>,
+}
+impl<Arg> PartialEq for AsyncDispatchAtom<Arg> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+impl<Arg> Eq for AsyncDispatchAtom<Arg> {}
+impl<Arg> Hash for AsyncDispatchAtom<Arg> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+impl<Arg> AsyncDispatchAtom<Arg> {
+    pub fn new<F, Fut>(setter: F) -> Self
+    where
+        F: Fn(Setter, Arc<Arg>) -> Fut + 'static + Send + Sync,
+        Fut: Future<Output = ()> + 'static + Send,
+    {
+        Self {
+            id: Arc::new(AtomId::new()),
+            dispatch: Box::new(move |s, arg| Box::pin(setter(s, arg))),
+        }
+    }
+}
+impl<Arg> Atom for AsyncDispatchAtom<Arg> {
+    fn get_id(&self) -> Arc<AtomId> {
+        self.id.clone()
+    }
+}
@@ -1,24 +1,27 @@
-use std::cell::RefCell;
-use std::rc::Rc;
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::{Arc, Weak};
+use std::sync::{Arc, Mutex, Weak};
 
 use weak_table::WeakKeyHashMap;
 
-use crate::atom_base::{AtomId, ReadAtom};
-use crate::dispatch_atom::{DispatchAtom, DispatchWithReturnAtom};
+use crate::atom_base::{AtomId, ReadAtom, WriteAtom};
+use crate::dispatch_atom::DispatchWithReturnAtom;
 use crate::jotai_store::JotaiStore;
 use crate::primitive_atom::PrimitiveAtom;
 
+// Per dependency: returns whether it has changed since it was read
+pub(crate) type Tracked =
+    Arc<Mutex<WeakKeyHashMap<Weak<AtomId>, Arc<dyn Fn() -> bool + Send + Sync>>>>;
+
 static NEXT_GETTER_ID: AtomicUsize = AtomicUsize::new(0);
 fn new_getter_id() -> usize {
     NEXT_GETTER_ID.fetch_add(1, Ordering::Relaxed)
 }
+#[derive(Clone)]
 pub struct Getter {
     pub(crate) id: usize,
-    atom_id: Arc<AtomId>,
-    store: Arc<JotaiStore>,
-    tracked: Rc<RefCell<WeakKeyHashMap<Weak<AtomId>, Box<dyn Fn() -> bool>>>>,
+    pub(crate) atom_id: Arc<AtomId>,
+    pub(crate) store: Arc<JotaiStore>,
+    tracked: Tracked,
 }
 impl Getter {
     pub(crate) fn new(store: Arc<JotaiStore>, atom_id: Arc<AtomId>) -> Self {
@@ -26,51 +29,73 @@ impl Getter {
             id: new_getter_id(),
             store,
             atom_id,
-            tracked: Rc::new(RefCell::new(WeakKeyHashMap::new())),
+            tracked: Arc::new(Mutex::new(WeakKeyHashMap::new())),
         }
     }
-    pub fn get<T: 'static + PartialEq + Send + Sync>(&self, atom: Arc<dyn ReadAtom<T>>) -> Arc<T> {
+    pub fn get<T: 'static + Send + Sync>(&self, atom: Arc<dyn ReadAtom<T>>) -> Arc<T> {
+        // Getters can outlive the read (async atoms), so take the lock for the whole tracking
+        let _guard = self.store.lock();
         let store = self.store.clone();
         let result = store.clone().get(&*atom);
+        store.track_mount(&atom);
         let value = result.clone();
         let atom_c = atom.clone();
-        self.tracked.borrow_mut().insert(
+        // Weak, the store holds on to this closure
+        let store = Arc::downgrade(&store);
+        self.tracked.lock().unwrap().insert(
             atom.get_id(),
-            Box::new(move || {
-                let current_value = store.clone().get(&*atom_c);
-                return current_value != value;
+            Arc::new(move || {
+                let Some(store) = store.upgrade() else {
+                    return true;
+                };
+                let current_value = store.get(&*atom_c);
+                return !atom_c.is_equal(&current_value, &value);
             }),
         );
         self.store
             .update_deps(self.atom_id.clone(), self.tracked.clone(), &self.id);
         return result;
     }
+    /// Reads a fallible atom, so its error can be propagated with `?`
+    pub fn try_get<T, E>(&self, atom: Arc<dyn ReadAtom<Result<T, E>>>) -> Result<T, E>
+    where
+        T: Clone + 'static + Send + Sync,
+        E: Clone + 'static + Send + Sync,
+    {
+        (*self.get(atom)).clone()
+    }
 }
 pub struct Setter {
-    store: Arc<JotaiStore>,
+    pub(crate) store: Arc<JotaiStore>,
 }
 impl Setter {
     pub(crate) fn new(store: Arc<JotaiStore>) -> Self {
         Self { store }
     }
-    pub fn get<T: 'static + PartialEq + Send + Sync>(&self, atom: Arc<dyn ReadAtom<T>>) -> Arc<T> {
+    pub fn get<T: 'static + Send + Sync>(&self, atom: Arc<dyn ReadAtom<T>>) -> Arc<T> {
         return self.store.clone().get(&*atom);
     }
-    pub fn set<Arg: PartialEq + 'static, Return>(&self, atom: &DispatchAtom<Arg>, arg: Arc<Arg>) {
+    pub fn set<Arg: 'static>(&self, atom: &(impl WriteAtom<Arg> + ?Sized), arg: Arc<Arg>) {
         return self.store.clone().set(atom, arg);
     }
-    pub fn set_and_return<Arg: PartialEq + 'static, Return>(
+    pub fn set_and_return<Arg: 'static, Return>(
         &self,
         atom: &DispatchWithReturnAtom<Arg, Return>,
         arg: Arc<Arg>,
     ) -> Return {
         return self.store.clone().set_and_return(atom, arg);
     }
-    pub fn set_primitive<T: PartialEq + 'static + Send + Sync>(
-        &self,
-        atom: &PrimitiveAtom<T>,
-        arg: Arc<T>,
-    ) {
+    pub fn set_primitive<T: 'static + Send + Sync>(&self, atom: &PrimitiveAtom<T>, arg: Arc<T>) {
         return self.store.clone().set_primitive(atom, arg);
     }
+    /// Applies the writes in `f` together, e.g. after an await in an async dispatch
+    pub fn batch<R>(&self, f: impl FnOnce(&mut Setter) -> R) -> R {
+        self.store.clone().batch(f)
+    }
+    pub fn reset<T: 'static + Send + Sync>(&self, atom: &PrimitiveAtom<T>) {
+        self.store.clone().reset(atom);
+    }
+    pub fn refresh<T: 'static + Send + Sync>(&self, atom: &(impl ReadAtom<T> + ?Sized)) {
+        self.store.clone().refresh(atom);
+    }
 }
@@ -0,0 +1,187 @@
+use std::any::Any;
+use std::collections::VecDeque;
+use std::sync::{Arc, Weak};
+
+use weak_table::WeakKeyHashMap;
+
+use crate::atom_base::*;
+
+pub(crate) type AnyValue = Arc<dyn Any + Send + Sync>;
+
+/// Values of every primitive atom a store knew about at capture time
+pub struct Snapshot {
+    pub(crate) values: WeakKeyHashMap<Weak<AtomId>, AnyValue>,
+}
+impl Snapshot {
+    pub fn get<T: 'static + Send + Sync>(&self, atom: &impl Atom) -> Option<Arc<T>> {
+        self.values
+            .get(&atom.get_id())
+            .cloned()
+            .and_then(|v| v.downcast::<T>().ok())
+    }
+}
+
+pub(crate) struct Change {
+    pub(crate) atom_id: Weak<AtomId>,
+    pub(crate) before: AnyValue,
+    pub(crate) after: AnyValue,
+}
+
+pub(crate) struct History {
+    limit: usize,
+    undo: VecDeque<Vec<Change>>,
+    redo: Vec<Vec<Change>>,
+    // Writes of the current batch, one batch is one undo step
+    pending: Vec<Change>,
+}
+impl History {
+    pub(crate) fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            undo: VecDeque::new(),
+            redo: vec![],
+            pending: vec![],
+        }
+    }
+
+    pub(crate) fn record(&mut self, atom_id: &Arc<AtomId>, before: AnyValue, after: AnyValue) {
+        let existing = self
+            .pending
+            .iter_mut()
+            .find(|c| c.atom_id.upgrade().as_ref() == Some(atom_id));
+        if let Some(change) = existing {
+            change.after = after;
+            return;
+        }
+        self.pending.push(Change {
+            atom_id: Arc::downgrade(atom_id),
+            before,
+            after,
+        });
+    }
+
+    pub(crate) fn commit(&mut self) {
+        if self.pending.is_empty() {
+            return;
+        }
+        self.undo.push_back(std::mem::take(&mut self.pending));
+        if self.undo.len() > self.limit {
+            self.undo.pop_front();
+        }
+        self.redo.clear();
+    }
+
+    pub(crate) fn take_undo(&mut self) -> Option<Vec<Change>> {
+        self.undo.pop_back()
+    }
+    pub(crate) fn push_redo(&mut self, changes: Vec<Change>) {
+        self.redo.push(changes);
+    }
+    pub(crate) fn take_redo(&mut self) -> Option<Vec<Change>> {
+        self.redo.pop()
+    }
+    pub(crate) fn push_undo(&mut self, changes: Vec<Change>) {
+        self.undo.push_back(changes);
+    }
+
+    pub(crate) fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+    pub(crate) fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use crate::*;
+
+    #[test]
+    fn test_snapshot_restore() {
+        let store = JotaiStore::new();
+        let counter_atom = Arc::new(atom(1));
+        let name_atom = Arc::new(atom("a".to_string()));
+        let double_atom = Arc::new(select_atom({
+            let counter_atom = counter_atom.clone();
+            move |getter| *getter.get(counter_atom.clone()) * 2
+        }));
+        let sub_counter = Arc::new(Mutex::new(0));
+        let _dispose = store.clone().sub(double_atom.clone(), {
+            let counter = sub_counter.clone();
+            move |_| *counter.lock().unwrap() += 1
+        });
+        store.clone().set_primitive(&counter_atom, Arc::new(5));
+        let snapshot = store.snapshot();
+        assert_eq!(snapshot.get::<i32>(&*counter_atom), Some(Arc::new(5)));
+        assert_eq!(snapshot.get::<String>(&*name_atom), None);
+
+        store.clone().set_primitive(&counter_atom, Arc::new(7));
+        store
+            .clone()
+            .set_primitive(&name_atom, Arc::new("b".to_string()));
+        assert_eq!(*sub_counter.lock().unwrap(), 2);
+
+        store.restore(&snapshot);
+        assert_eq!(*store.clone().get(&*double_atom), 10);
+        assert_eq!(*sub_counter.lock().unwrap(), 3);
+        // Not set when the snapshot was taken, so it's back to its default
+        assert_eq!(*store.clone().get(&*name_atom), "a");
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let store = JotaiStore::new();
+        let counter_atom = Arc::new(atom(1));
+        let counter_2_atom = Arc::new(atom(1));
+        let set_both_atom = dispatch_atom({
+            let counter_atom = counter_atom.clone();
+            let counter_2_atom = counter_2_atom.clone();
+            move |setter, arg: Arc<i32>| {
+                setter.set_primitive(&counter_atom, arg.clone());
+                setter.set_primitive(&counter_2_atom, arg.clone());
+            }
+        });
+        store.clone().set_primitive(&counter_atom, Arc::new(2));
+        assert!(!store.can_undo());
+
+        store.enable_history(10);
+        store.clone().set_primitive(&counter_atom, Arc::new(3));
+        store.clone().set(&set_both_atom, Arc::new(4));
+        assert!(store.can_undo());
+
+        // The dispatch is a single step
+        assert!(store.undo());
+        assert_eq!(*store.clone().get(&*counter_atom), 3);
+        assert_eq!(*store.clone().get(&*counter_2_atom), 1);
+        assert!(store.undo());
+        assert_eq!(*store.clone().get(&*counter_atom), 2);
+        assert!(!store.undo());
+
+        assert!(store.redo());
+        assert!(store.redo());
+        assert_eq!(*store.clone().get(&*counter_atom), 4);
+        assert_eq!(*store.clone().get(&*counter_2_atom), 4);
+        assert!(!store.redo());
+
+        // A new write drops the redo stack
+        store.undo();
+        store.clone().set_primitive(&counter_atom, Arc::new(9));
+        assert!(!store.can_redo());
+    }
+
+    #[test]
+    fn test_history_limit() {
+        let store = JotaiStore::new();
+        let counter_atom = Arc::new(atom(0));
+        store.enable_history(2);
+        for i in 1..=5 {
+            store.clone().set_primitive(&counter_atom, Arc::new(i));
+        }
+        assert!(store.undo());
+        assert!(store.undo());
+        assert!(!store.undo());
+        assert_eq!(*store.clone().get(&*counter_atom), 3);
+    }
+}
@@ -0,0 +1,194 @@
+use std::any::Any;
+use std::fmt::Write;
+use std::sync::Arc;
+
+use crate::atom_base::AtomId;
+
+#[derive(Debug, Clone)]
+pub struct AtomInfo {
+    pub id: AtomId,
+    pub label: Option<String>,
+    pub value: Option<Arc<dyn Any + Send + Sync>>,
+    pub primitive: bool,
+    /// Subscribed to, directly or through a dependent
+    pub mounted: bool,
+    /// A dependency changed since the last read, it recomputes on the next get
+    pub stale: bool,
+    pub subscribers: usize,
+    pub dependencies: Vec<AtomId>,
+    pub dependents: Vec<AtomId>,
+}
+
+pub(crate) fn graph_to_json(atoms: &[AtomInfo]) -> String {
+    let mut out = String::from("{\"atoms\":[");
+    for (i, atom) in atoms.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        let label = match &atom.label {
+            Some(label) => format!("\"{}\"", escape(label)),
+            None => "null".to_string(),
+        };
+        let _ = write!(
+            out,
+            "{{\"id\":{},\"label\":{},\"has_value\":{},\"primitive\":{},\"mounted\":{},\"stale\":{},\"subscribers\":{},\"dependencies\":[{}],\"dependents\":[{}]}}",
+            atom.id,
+            label,
+            atom.value.is_some(),
+            atom.primitive,
+            atom.mounted,
+            atom.stale,
+            atom.subscribers,
+            join_ids(&atom.dependencies),
+            join_ids(&atom.dependents),
+        );
+    }
+    out.push_str("]}");
+    out
+}
+
+/// Edges point from a dependency to its dependent, i.e. the direction changes propagate
+pub(crate) fn graph_to_dot(atoms: &[AtomInfo]) -> String {
+    let mut out = String::from("digraph jotai {\n");
+    for atom in atoms {
+        let name = match &atom.label {
+            Some(label) => format!("{} ({})", escape(label), atom.id),
+            None => atom.id.to_string(),
+        };
+        let mut attrs = vec![format!("label=\"{}\"", name)];
+        if atom.primitive {
+            attrs.push("shape=box".to_string());
+        }
+        if atom.mounted {
+            attrs.push("style=bold".to_string());
+        }
+        if atom.stale {
+            attrs.push("color=red".to_string());
+        }
+        let _ = writeln!(out, "  \"{}\" [{}];", atom.id, attrs.join(", "));
+    }
+    for atom in atoms {
+        for dep in &atom.dependencies {
+            let _ = writeln!(out, "  \"{}\" -> \"{}\";", dep, atom.id);
+        }
+    }
+    out.push_str("}\n");
+    out
+}
+
+fn join_ids(ids: &[AtomId]) -> String {
+    ids.iter()
+        .map(|id| id.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn escape(s: &str) -> String {
+    let mut out = String::new();
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use crate::*;
+
+    #[test]
+    fn test_inspect() {
+        let store = JotaiStore::new();
+        let counter_atom = Arc::new(atom(1).with_label("counter"));
+        let double_atom = Arc::new(
+            select_atom({
+                let counter_atom = counter_atom.clone();
+                move |getter| *getter.get(counter_atom.clone()) * 2
+            })
+            .with_label("double"),
+        );
+        let unused_atom = Arc::new(select_atom({
+            let counter_atom = counter_atom.clone();
+            move |getter| *getter.get(counter_atom.clone()) + 1
+        }));
+        assert_eq!(counter_atom.get_id().label(), Some("counter".to_string()));
+
+        let sub_counter = Arc::new(Mutex::new(0));
+        let _dispose = store.clone().sub(double_atom.clone(), {
+            let counter = sub_counter.clone();
+            move |_| *counter.lock().unwrap() += 1
+        });
+        store.clone().get(&*unused_atom);
+        store.clone().set_primitive(&counter_atom, Arc::new(2));
+
+        let atoms = store.inspect();
+        assert_eq!(atoms.len(), 3);
+        let counter = atoms
+            .iter()
+            .find(|a| a.id == *counter_atom.get_id())
+            .unwrap();
+        assert!(counter.primitive);
+        assert!(counter.mounted);
+        assert!(!counter.stale);
+        assert_eq!(counter.subscribers, 0);
+        assert_eq!(counter.dependencies, vec![]);
+        assert_eq!(
+            counter.dependents,
+            vec![*double_atom.get_id(), *unused_atom.get_id()]
+        );
+        assert_eq!(
+            counter.value.clone().unwrap().downcast::<i32>().ok(),
+            Some(Arc::new(2))
+        );
+        let double = atoms
+            .iter()
+            .find(|a| a.id == *double_atom.get_id())
+            .unwrap();
+        assert!(double.mounted);
+        assert!(!double.stale);
+        assert_eq!(double.subscribers, 1);
+        assert_eq!(double.dependencies, vec![*counter_atom.get_id()]);
+        let unused = atoms
+            .iter()
+            .find(|a| a.id == *unused_atom.get_id())
+            .unwrap();
+        assert!(!unused.mounted);
+        assert!(unused.stale);
+        assert_eq!(unused.label, None);
+    }
+
+    #[test]
+    fn test_graph_export() {
+        let store = JotaiStore::new();
+        let counter_atom = Arc::new(atom(1).with_label("counter \"a\""));
+        let double_atom = Arc::new(select_atom({
+            let counter_atom = counter_atom.clone();
+            move |getter| *getter.get(counter_atom.clone()) * 2
+        }));
+        store.clone().get(&*double_atom);
+        let counter_id = counter_atom.get_id();
+        let double_id = double_atom.get_id();
+
+        assert_eq!(
+            store.graph_json(),
+            format!(
+                "{{\"atoms\":[{{\"id\":{counter_id},\"label\":\"counter \\\"a\\\"\",\"has_value\":true,\"primitive\":true,\"mounted\":false,\"stale\":false,\"subscribers\":0,\"dependencies\":[],\"dependents\":[{double_id}]}},{{\"id\":{double_id},\"label\":null,\"has_value\":true,\"primitive\":false,\"mounted\":false,\"stale\":false,\"subscribers\":0,\"dependencies\":[{counter_id}],\"dependents\":[]}}]}}"
+            )
+        );
+        assert_eq!(
+            store.graph_dot(),
+            format!(
+                "digraph jotai {{\n  \"{counter_id}\" [label=\"counter \\\"a\\\" ({counter_id})\", shape=box];\n  \"{double_id}\" [label=\"{double_id}\"];\n  \"{counter_id}\" -> \"{double_id}\";\n}}\n"
+            )
+        );
+    }
+}
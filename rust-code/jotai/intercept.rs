@@ -0,0 +1,44 @@
+use std::time::Duration;
+
+use crate::atom_base::AtomId;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StoreEventKind {
+    /// A primitive atom was written with `set_primitive`, whether or not its value changed
+    Set,
+    /// A write atom ran through `set`, `set_and_return` or `set_async`
+    Dispatch,
+    /// An atom's read ran, including the first read of a primitive's default
+    Recompute,
+    /// The atom's subscribers were called
+    Notify,
+}
+
+/// Reported to interceptors once the operation is done, so nested operations come first
+#[derive(Debug, Clone)]
+pub struct StoreEvent {
+    pub kind: StoreEventKind,
+    pub atom_id: AtomId,
+    pub label: Option<String>,
+    pub duration: Duration,
+}
+
+// `Instant::now` panics on wasm32-unknown-unknown, durations are reported as zero there
+pub(crate) struct Timer {
+    #[cfg(not(target_arch = "wasm32"))]
+    start: std::time::Instant,
+}
+impl Timer {
+    pub(crate) fn start() -> Self {
+        Self {
+            #[cfg(not(target_arch = "wasm32"))]
+            start: std::time::Instant::now(),
+        }
+    }
+    pub(crate) fn elapsed(&self) -> Duration {
+        #[cfg(not(target_arch = "wasm32"))]
+        return self.start.elapsed();
+        #[cfg(target_arch = "wasm32")]
+        return Duration::ZERO;
+    }
+}
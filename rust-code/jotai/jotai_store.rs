@@ -1,39 +1,136 @@
-use parking_lot::ReentrantMutex;
-use send_wrapper::SendWrapper;
+use futures::channel::mpsc::unbounded;
+use parking_lot::{ReentrantMutex, ReentrantMutexGuard};
 use std::any::Any;
 use std::cell::RefCell;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::future::Future;
 use std::rc::Rc;
 use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
 use weak_table::{WeakHashSet, WeakKeyHashMap};
 
 use crate::atom_base::*;
 use crate::dispatch_atom::*;
 use crate::getter_setter::*;
+use crate::history::*;
+use crate::inspect::*;
+use crate::intercept::*;
 use crate::primitive_atom::*;
 use crate::subscription_set::SubscriptionSet;
+use crate::watch::AtomStream;
+
+type Cleanup = Box<dyn FnOnce() + Send>;
+type Mount = Rc<dyn Fn(&Arc<JotaiStore>) -> Option<Cleanup>>;
+type OnSetAny = Rc<dyn Fn(&AnyValue)>;
 
 pub struct JotaiStore {
+    this: Weak<JotaiStore>,
     map: Rc<RefCell<WeakKeyHashMap<Weak<AtomId>, Arc<dyn Any + Send + Sync>>>>,
     deps_manager: Rc<DepsManager>,
-    subs: Rc<RefCell<WeakKeyHashMap<Weak<AtomId>, Rc<SubscriptionSet<()>>>>>,
-    mutex: ReentrantMutex<()>,
+    subs: Rc<RefCell<WeakKeyHashMap<Weak<AtomId>, Arc<SubscriptionSet<()>>>>>,
+    batch: Rc<RefCell<Batch>>,
+    mounts: Rc<RefCell<WeakKeyHashMap<Weak<AtomId>, Cleanup>>>,
+    // How to mount every atom that was subscribed to or read by another atom
+    mountable: Rc<RefCell<WeakKeyHashMap<Weak<AtomId>, Mount>>>,
+    // Primitive atoms and the first value they had, which a snapshot without them restores
+    primitives: Rc<RefCell<WeakKeyHashMap<Weak<AtomId>, AnyValue>>>,
+    // Type erased `on_set` of written atoms, so restored values are persisted too
+    on_sets: Rc<RefCell<WeakKeyHashMap<Weak<AtomId>, OnSetAny>>>,
+    history: Rc<RefCell<Option<History>>>,
+    failed: Rc<RefCell<WeakHashSet<Weak<AtomId>>>>,
+    interceptors: Arc<SubscriptionSet<StoreEvent>>,
+    // Shared by a store and all its children, so crossing between them can't deadlock
+    mutex: Arc<ReentrantMutex<()>>,
+    parent: Option<Arc<JotaiStore>>,
+    scoped: WeakHashSet<Weak<AtomId>>,
+    // Children hear about changes to the parent's atoms, which their own atoms may depend on
+    children: Rc<RefCell<Vec<Weak<JotaiStore>>>>,
 }
 impl JotaiStore {
     pub fn new() -> Arc<Self> {
-        Arc::new(Self {
+        Arc::new_cyclic(|this| Self {
+            this: this.clone(),
             map: Rc::new(RefCell::new(WeakKeyHashMap::new())),
             deps_manager: Rc::new(DepsManager::new()),
             subs: Rc::new(RefCell::new(WeakKeyHashMap::new())),
-            mutex: ReentrantMutex::new(()),
+            batch: Rc::new(RefCell::new(Batch::default())),
+            mounts: Rc::new(RefCell::new(WeakKeyHashMap::new())),
+            mountable: Rc::new(RefCell::new(WeakKeyHashMap::new())),
+            primitives: Rc::new(RefCell::new(WeakKeyHashMap::new())),
+            on_sets: Rc::new(RefCell::new(WeakKeyHashMap::new())),
+            history: Rc::new(RefCell::new(None)),
+            failed: Rc::new(RefCell::new(WeakHashSet::new())),
+            interceptors: Arc::new(SubscriptionSet::new()),
+            mutex: Arc::new(ReentrantMutex::new(())),
+            parent: None,
+            scoped: WeakHashSet::new(),
+            children: Rc::new(RefCell::new(vec![])),
         })
     }
 
-    pub fn get<T: 'static + PartialEq + Send + Sync>(
+    /// Creates a child store that keeps its own state for the `scoped` primitive atoms. Other
+    /// primitive atoms are read, written and subscribed to in this store. Derived atoms are
+    /// always computed in the child, like in jotai-scope, so the ones that depend on a scoped atom
+    /// read its scoped value without having to be listed.
+    pub fn child(self: Arc<Self>, scoped: impl IntoIterator<Item = Arc<AtomId>>) -> Arc<Self> {
+        let _guard = self.mutex.lock();
+        let mut scoped_ids = WeakHashSet::new();
+        for atom_id in scoped {
+            scoped_ids.insert(atom_id);
+        }
+        let child = Arc::new_cyclic(|this| Self {
+            this: this.clone(),
+            map: Rc::new(RefCell::new(WeakKeyHashMap::new())),
+            deps_manager: Rc::new(DepsManager::new()),
+            subs: Rc::new(RefCell::new(WeakKeyHashMap::new())),
+            batch: Rc::new(RefCell::new(Batch::default())),
+            mounts: Rc::new(RefCell::new(WeakKeyHashMap::new())),
+            mountable: Rc::new(RefCell::new(WeakKeyHashMap::new())),
+            primitives: Rc::new(RefCell::new(WeakKeyHashMap::new())),
+            on_sets: Rc::new(RefCell::new(WeakKeyHashMap::new())),
+            history: Rc::new(RefCell::new(None)),
+            failed: Rc::new(RefCell::new(WeakHashSet::new())),
+            interceptors: Arc::new(SubscriptionSet::new()),
+            mutex: self.mutex.clone(),
+            parent: Some(self.clone()),
+            scoped: scoped_ids,
+            children: Rc::new(RefCell::new(vec![])),
+        });
+        let mut children = self.children.borrow_mut();
+        children.retain(|child| child.strong_count() > 0);
+        children.push(Arc::downgrade(&child));
+        child
+    }
+
+    /// The store that owns the atom's state, if it's not this one
+    fn delegate(&self, atom: &(impl Atom + ?Sized)) -> Option<&Arc<JotaiStore>> {
+        self.delegate_state(&atom.get_id())
+            .filter(|_| atom.is_primitive())
+    }
+
+    /// Like `delegate`, for writes, which only ever go to primitive atoms
+    fn delegate_state(&self, atom_id: &AtomId) -> Option<&Arc<JotaiStore>> {
+        self.parent
+            .as_ref()
+            .filter(|_| !self.scoped.contains(atom_id))
+    }
+
+    fn children(&self) -> Vec<Arc<JotaiStore>> {
+        let children = self.children.borrow();
+        children
+            .iter()
+            .filter_map(|child| child.upgrade())
+            .collect()
+    }
+
+    pub fn get<T: 'static + Send + Sync>(
         self: Arc<Self>,
         atom: &(impl ReadAtom<T> + ?Sized),
     ) -> Arc<T> {
-        let _ = self.mutex.lock();
+        let _guard = self.mutex.lock();
+        if let Some(parent) = self.delegate(atom) {
+            return parent.clone().get(atom);
+        }
         let is_stale = self.deps_manager.check_stale(&atom.get_id());
         let cached_value = self
             .map
@@ -56,117 +153,675 @@ impl JotaiStore {
                 .current_getter_id
                 .borrow_mut()
                 .insert(atom.get_id(), getter.id);
-            read(&mut getter)
+            self.intercept(StoreEventKind::Recompute, &atom.get_id(), || {
+                read(&mut getter)
+            })
         });
         self.map
             .borrow_mut()
             .insert(atom.get_id().clone(), value.clone());
+        if atom.is_primitive() {
+            self.primitives
+                .borrow_mut()
+                .entry(atom.get_id())
+                .or_insert_with(|| value.clone());
+        }
+        self.set_failed(atom.get_id(), atom.is_error(&value));
 
-        if is_stale && cached_value.clone().is_some_and(|v| v == value.clone()) {
+        if is_stale
+            && cached_value
+                .as_ref()
+                .is_some_and(|v| atom.is_equal(v, &value))
+        {
             return value;
         }
 
         if cached_value.is_some() {
-            if let Some(closures) = self.subs.borrow().get(&atom.get_id()) {
-                closures.notify(&());
-            }
+            self.notify(atom.get_id());
         }
 
         return value;
     }
 
-    pub fn set_primitive<T: 'static + PartialEq + Send + Sync>(
+    pub fn set_primitive<T: 'static + Send + Sync>(&self, atom: &PrimitiveAtom<T>, arg: Arc<T>) {
+        let _guard = self.mutex.lock();
+        // The first write needs a value to undo to, e.g. what a storage atom loaded
+        if let Some(store) = self.this.upgrade() {
+            store.get(atom);
+        }
+        self.set_value(atom.get_id(), arg, &*atom.equals, atom.on_set.as_ref());
+    }
+
+    /// Writes the atom's initial value like `set_primitive` would, so dependents and subscribers
+    /// only hear about it if it differs from the current value. Atoms without one go back to what
+    /// their read computes now.
+    pub fn reset<T: 'static + Send + Sync>(self: Arc<Self>, atom: &PrimitiveAtom<T>) {
+        let _guard = self.mutex.lock();
+        let initial = match &atom.initial {
+            Some(initial) => Arc::new(initial()),
+            None => {
+                let mut getter = Getter::new(self.clone(), atom.get_id());
+                Arc::new((atom.get_read())(&mut getter))
+            }
+        };
+        self.set_primitive(atom, initial);
+    }
+
+    /// Drops the cached value of a derived atom and reads it again, e.g. after the data it reads
+    /// outside the store changed. Dependents and subscribers are notified if the value changed,
+    /// child stores refresh their own copy too.
+    pub fn refresh<T: 'static + Send + Sync>(self: Arc<Self>, atom: &(impl ReadAtom<T> + ?Sized)) {
+        let _guard = self.mutex.lock();
+        if let Some(parent) = self.delegate(atom) {
+            return parent.clone().refresh(atom);
+        }
+        if !atom.is_primitive() {
+            for child in self.children() {
+                child.refresh(atom);
+            }
+        }
+        let atom_id = atom.get_id();
+        let Some(before) = self.map.borrow_mut().remove(&atom_id) else {
+            // Not cached, the next read computes it anyway
+            return;
+        };
+        self.start_batch();
+        let value = self.clone().get(atom);
+        let unchanged = before
+            .downcast::<T>()
+            .is_ok_and(|before| atom.is_equal(&before, &value));
+        if !unchanged {
+            self.mark_changed(atom_id);
+        }
+        self.end_batch();
+    }
+
+    /// Writes a primitive value and runs `on_set` if it changed, every write of a value that
+    /// atoms hold goes through here. The atom has to have been read in this store before, so
+    /// there's a value to undo to.
+    pub(crate) fn set_value<T: 'static + Send + Sync>(
         &self,
-        atom: &PrimitiveAtom<T>,
+        atom_id: Arc<AtomId>,
         arg: Arc<T>,
-    ) {
-        let _ = self.mutex.lock();
+        equals: &dyn Fn(&T, &T) -> bool,
+        on_set: Option<&OnSet<T>>,
+    ) -> bool {
+        let _guard = self.mutex.lock();
+        let id = *atom_id;
+        self.intercept(StoreEventKind::Set, &id, || {
+            self.write_value(atom_id, arg, equals, on_set)
+        })
+    }
+
+    fn write_value<T: 'static + Send + Sync>(
+        &self,
+        atom_id: Arc<AtomId>,
+        arg: Arc<T>,
+        equals: &dyn Fn(&T, &T) -> bool,
+        on_set: Option<&OnSet<T>>,
+    ) -> bool {
+        if let Some(parent) = self.delegate_state(&atom_id) {
+            return parent.write_value(atom_id, arg, equals, on_set);
+        }
         {
             // limit this borrow to just the check
             let map = self.map.borrow();
-            let cached_value = map.get(&*atom.get_id()).and_then(|v| v.downcast_ref::<T>());
-            if cached_value.is_some_and(|v| *v == *arg.clone()) {
-                return;
+            let cached_value = map.get(&*atom_id).and_then(|v| v.downcast_ref::<T>());
+            if cached_value.is_some_and(|v| equals(v, &arg)) {
+                return false;
+            }
+        }
+
+        self.start_batch();
+        let before = self.map.borrow_mut().insert(atom_id.clone(), arg.clone());
+        let before = before.unwrap_or_else(|| arg.clone() as AnyValue);
+        self.primitives
+            .borrow_mut()
+            .entry(atom_id.clone())
+            .or_insert_with(|| before.clone());
+        self.record_change(&atom_id, before, arg.clone());
+        if let Some(on_set) = on_set {
+            on_set(&arg);
+            let on_set = on_set.clone();
+            let on_set_any: OnSetAny = Rc::new(move |value| {
+                if let Some(value) = value.downcast_ref::<T>() {
+                    on_set(value);
+                }
+            });
+            self.on_sets
+                .borrow_mut()
+                .insert(atom_id.clone(), on_set_any);
+        }
+        self.mark_changed(atom_id);
+        self.end_batch();
+        true
+    }
+
+    /// Recomputes every atom whose cached value is an error. Dependents and subscribers are only
+    /// notified if the result changed, atoms that didn't fail are left alone.
+    pub fn retry(&self) {
+        let _guard = self.mutex.lock();
+        let failed: Vec<_> = self.failed.borrow().iter().collect();
+        if failed.is_empty() {
+            return;
+        }
+        self.start_batch();
+        for atom_id in failed {
+            self.deps_manager.invalidate(atom_id.clone());
+            let stale = self.deps_manager.propagate_stale(atom_id);
+            self.batch.borrow_mut().stale.extend(stale);
+        }
+        self.end_batch();
+    }
+
+    fn set_failed(&self, atom_id: Arc<AtomId>, failed: bool) {
+        if failed {
+            self.failed.borrow_mut().insert(atom_id);
+        } else {
+            self.failed.borrow_mut().remove(&atom_id);
+        }
+    }
+
+    /// Captures the values of all primitive atoms in this store
+    pub fn snapshot(&self) -> Snapshot {
+        let _guard = self.mutex.lock();
+        let map = self.map.borrow();
+        let mut values = WeakKeyHashMap::new();
+        for atom_id in self.primitives.borrow().keys() {
+            if let Some(value) = map.get(&atom_id) {
+                values.insert(atom_id, value.clone());
+            }
+        }
+        Snapshot { values }
+    }
+
+    /// Sets every primitive atom back to its snapshot value, atoms that didn't have a value yet
+    /// go back to the first value they had. Dependents recompute, subscribers are notified and
+    /// `on_set` runs as usual.
+    pub fn restore(&self, snapshot: &Snapshot) {
+        let _guard = self.mutex.lock();
+        let changes: Vec<_> = self
+            .primitives
+            .borrow()
+            .iter()
+            .map(|(atom_id, first)| {
+                let value = snapshot.values.get(&atom_id).unwrap_or(first).clone();
+                (atom_id, value)
+            })
+            .collect();
+        self.start_batch();
+        for (atom_id, value) in changes {
+            if let Some(before) = self.apply_value(&atom_id, value.clone()) {
+                self.record_change(&atom_id, before, value);
             }
         }
+        self.end_batch();
+    }
+
+    /// Starts recording writes, each batch (e.g. a dispatch) is one step to undo or redo.
+    /// Only the most recent `limit` steps are kept.
+    pub fn enable_history(&self, limit: usize) {
+        let _guard = self.mutex.lock();
+        *self.history.borrow_mut() = Some(History::new(limit));
+    }
+
+    pub fn disable_history(&self) {
+        let _guard = self.mutex.lock();
+        *self.history.borrow_mut() = None;
+    }
 
-        self.map.borrow_mut().insert(atom.get_id().clone(), arg);
+    pub fn can_undo(&self) -> bool {
+        let _guard = self.mutex.lock();
+        self.history.borrow().as_ref().is_some_and(|h| h.can_undo())
+    }
+
+    pub fn can_redo(&self) -> bool {
+        let _guard = self.mutex.lock();
+        self.history.borrow().as_ref().is_some_and(|h| h.can_redo())
+    }
 
-        self.deps_manager.propagate_stale(atom.get_id().clone());
+    pub fn undo(&self) -> bool {
+        let _guard = self.mutex.lock();
+        let changes = self
+            .history
+            .borrow_mut()
+            .as_mut()
+            .and_then(|h| h.take_undo());
+        let Some(changes) = changes else {
+            return false;
+        };
+        self.apply_changes(changes.iter().rev().map(|c| (&c.atom_id, &c.before)));
+        if let Some(history) = self.history.borrow_mut().as_mut() {
+            history.push_redo(changes);
+        }
+        true
+    }
+
+    pub fn redo(&self) -> bool {
+        let _guard = self.mutex.lock();
+        let changes = self
+            .history
+            .borrow_mut()
+            .as_mut()
+            .and_then(|h| h.take_redo());
+        let Some(changes) = changes else {
+            return false;
+        };
+        self.apply_changes(changes.iter().map(|c| (&c.atom_id, &c.after)));
+        if let Some(history) = self.history.borrow_mut().as_mut() {
+            history.push_undo(changes);
+        }
+        true
+    }
+
+    fn apply_changes<'a>(&self, changes: impl Iterator<Item = (&'a Weak<AtomId>, &'a AnyValue)>) {
+        self.start_batch();
+        for (atom_id, value) in changes {
+            if let Some(atom_id) = atom_id.upgrade() {
+                self.apply_value(&atom_id, value.clone());
+            }
+        }
+        self.end_batch();
+    }
+
+    /// Writes a recorded value and runs the atom's `on_set`, must be called within a batch.
+    /// Returns the previous value if anything changed.
+    fn apply_value(&self, atom_id: &Arc<AtomId>, value: AnyValue) -> Option<AnyValue> {
+        let current = self.map.borrow().get(atom_id).cloned();
+        if current
+            .as_ref()
+            .is_some_and(|current| Arc::ptr_eq(current, &value))
+        {
+            return None;
+        }
+        self.map.borrow_mut().insert(atom_id.clone(), value.clone());
+        let on_set = self.on_sets.borrow().get(atom_id).cloned();
+        if let Some(on_set) = on_set {
+            on_set(&value);
+        }
+        self.mark_changed(atom_id.clone());
+        Some(current.unwrap_or(value))
+    }
 
-        if let Some(closures) = self.subs.borrow().get(&atom.get_id()) {
-            closures.notify(&());
+    fn record_change(&self, atom_id: &Arc<AtomId>, before: AnyValue, after: AnyValue) {
+        if let Some(history) = self.history.borrow_mut().as_mut() {
+            history.record(atom_id, before, after);
         }
     }
 
-    pub fn set<Arg: PartialEq + 'static>(self: Arc<Self>, atom: &DispatchAtom<Arg>, arg: Arc<Arg>) {
-        let _ = self.mutex.lock();
+    /// Applies every write in `f` before any subscriber is notified, so subscribers only ever see
+    /// the final state. Each affected subscriber is notified once at the end.
+    pub fn batch<R>(self: Arc<Self>, f: impl FnOnce(&mut Setter) -> R) -> R {
+        let _guard = self.mutex.lock();
+        self.start_batch();
         let mut setter = Setter::new(self.clone());
-        (atom.dispatch)(&mut setter, arg);
+        let result = f(&mut setter);
+        self.end_batch();
+        result
     }
 
-    pub fn set_and_return<Arg: PartialEq + 'static, Return>(
+    pub fn set<Arg: 'static>(
+        self: Arc<Self>,
+        atom: &(impl WriteAtom<Arg> + ?Sized),
+        arg: Arc<Arg>,
+    ) {
+        let store = self.clone();
+        self.intercept(StoreEventKind::Dispatch, &atom.get_id(), || {
+            store.batch(|setter| atom.write(setter, arg))
+        });
+    }
+
+    pub fn set_and_return<Arg: 'static, Return>(
         self: Arc<Self>,
         atom: &DispatchWithReturnAtom<Arg, Return>,
         arg: Arc<Arg>,
     ) -> Return {
-        let _ = self.mutex.lock();
-        let mut setter = Setter::new(self.clone());
-        (atom.dispatch)(&mut setter, arg)
+        let store = self.clone();
+        self.intercept(StoreEventKind::Dispatch, &atom.get_id(), || {
+            store.batch(|setter| (atom.dispatch)(setter, arg))
+        })
     }
 
-    pub fn sub<T: 'static + PartialEq + Send + Sync, F>(
+    /// Starts an async dispatch, it runs as the returned future is polled
+    pub fn set_async<Arg: 'static>(
         self: Arc<Self>,
-        atom: Arc<(impl ReadAtom<T> + ?Sized + 'static + Send + Sync)>,
+        atom: &AsyncDispatchAtom<Arg>,
+        arg: Arc<Arg>,
+    ) -> impl Future<Output = ()> + Send + 'static {
+        let atom_id = atom.get_id();
+        let future = (atom.dispatch)(Setter::new(self.clone()), arg);
+        async move {
+            let timer = Timer::start();
+            future.await;
+            self.report(StoreEventKind::Dispatch, &atom_id, timer.elapsed());
+        }
+    }
+
+    /// Calls `f` with every store event, e.g. to log writes or check invariants in tests. Returns
+    /// a function that removes it again, until it's called `f` stays registered even if the
+    /// function is dropped.
+    pub fn add_interceptor(
+        &self,
+        f: impl Fn(&StoreEvent) + 'static + Send + Sync,
+    ) -> Box<dyn FnOnce() + Send> {
+        self.interceptors.sub_until_removed(f)
+    }
+
+    /// Runs `f` and reports how long it took, skips the timing when nobody is listening
+    fn intercept<R>(&self, kind: StoreEventKind, atom_id: &AtomId, f: impl FnOnce() -> R) -> R {
+        if self.interceptors.is_empty() {
+            return f();
+        }
+        let timer = Timer::start();
+        let result = f();
+        self.report(kind, atom_id, timer.elapsed());
+        result
+    }
+
+    fn report(&self, kind: StoreEventKind, atom_id: &AtomId, duration: Duration) {
+        if self.interceptors.is_empty() {
+            return;
+        }
+        self.interceptors.notify(&StoreEvent {
+            kind,
+            atom_id: *atom_id,
+            label: atom_id.label(),
+            duration,
+        });
+    }
+
+    pub fn sub<T: 'static + Send + Sync, F>(
+        self: Arc<Self>,
+        atom: Arc<impl ReadAtom<T> + ?Sized + 'static>,
         on_change: F,
     ) -> impl Fn() + Send + Sync
     where
         F: Fn(&()) + 'static + Send + Sync,
     {
-        let _ = self.mutex.lock();
+        let _guard = self.mutex.lock();
+        if let Some(parent) = self.delegate(&*atom) {
+            let dispose: Box<dyn Fn() + Send + Sync> =
+                Box::new(parent.clone().sub(atom, on_change));
+            return dispose;
+        }
         let store = self.clone();
         let atom_c = atom.clone();
-        let dispose_dep = self.deps_manager.add_sub(atom.get_id(), move || {
+        self.deps_manager.add_sub(atom.get_id(), move || {
             store.clone().get(&*atom_c);
         });
-        let dispose_dep = Arc::new(Mutex::new(Some(dispose_dep)));
+        self.track_mount(&atom);
         let dispose_sub = self
             .subs
             .borrow_mut()
             .entry(atom.get_id())
-            .or_insert_with(|| Rc::new(SubscriptionSet::new()))
+            .or_insert_with(|| Arc::new(SubscriptionSet::new()))
             .sub(on_change);
-        let dispose_sub = Arc::new(Mutex::new(Some(dispose_sub)));
-        let dispose_sub = SendWrapper::new(dispose_sub);
-        let dispose_dep = SendWrapper::new(dispose_dep);
+        let dispose_sub = Mutex::new(Some(dispose_sub));
         self.clone().get(&*atom);
-        return move || {
-            let _ = self.mutex.lock();
-            if let Ok(mut guard) = dispose_sub.lock() {
-                if let Some(cleanup) = guard.take() {
-                    cleanup();
-                }
-            }
-            let closures = self.subs.borrow().get(&atom.get_id()).cloned();
+        self.update_mounts();
+        let store = Arc::downgrade(&self);
+        Box::new(move || {
+            let Some(store) = store.upgrade() else {
+                return;
+            };
+            let _guard = store.mutex.lock();
+            let Some(cleanup) = dispose_sub.lock().unwrap().take() else {
+                return;
+            };
+            cleanup();
+            let closures = store.subs.borrow().get(&atom.get_id()).cloned();
             if let Some(closures) = closures {
                 if closures.is_empty() {
-                    if let Ok(mut guard) = dispose_dep.lock() {
-                        if let Some(cleanup) = guard.take() {
-                            cleanup();
-                        }
-                    }
-                    self.subs.borrow_mut().remove(&atom.get_id());
+                    store.deps_manager.remove_sub(&atom.get_id());
+                    store.subs.borrow_mut().remove(&atom.get_id());
+                    store.update_mounts();
                 }
             }
-        };
+        })
     }
 
-    pub(crate) fn update_deps(
+    /// Remembers how to mount an atom, so it can be mounted along with a subscribed dependent
+    pub(crate) fn track_mount<T: 'static + Send + Sync>(
         &self,
+        atom: &Arc<impl ReadAtom<T> + ?Sized + 'static>,
+    ) {
+        let atom_ref = Arc::downgrade(atom);
+        let mount: Mount = Rc::new(move |store| {
+            let atom = atom_ref.upgrade()?;
+            match store.delegate(&*atom) {
+                // Mounted in the parent for as long as something here depends on it
+                Some(parent) => {
+                    let dispose = parent.clone().sub(atom, |_| {});
+                    Some(Box::new(dispose))
+                }
+                None => atom.on_mount(store),
+            }
+        });
+        self.mountable.borrow_mut().insert(atom.get_id(), mount);
+    }
+
+    /// Runs `on_mount` for atoms that are now subscribed to, directly or through a dependent, and
+    /// the cleanup of those that aren't anymore. Dependencies are mounted before their dependents.
+    fn update_mounts(&self) {
+        let Some(store) = self.this.upgrade() else {
+            return;
+        };
+        let mounted = self.deps_manager.mounted();
+        let unmounted: Vec<_> = self
+            .mounts
+            .borrow()
+            .keys()
+            .filter(|atom_id| !mounted.contains(atom_id))
+            .collect();
+        for atom_id in unmounted {
+            let cleanup = self.mounts.borrow_mut().remove(&atom_id);
+            if let Some(cleanup) = cleanup {
+                cleanup();
+            }
+        }
+        for atom_id in self.deps_manager.topological_order(mounted) {
+            if self.mounts.borrow().contains_key(&atom_id) {
+                continue;
+            }
+            // Marked first, so a write from on_mount can't mount it again
+            self.mounts
+                .borrow_mut()
+                .insert(atom_id.clone(), Box::new(|| {}));
+            let mount = self.mountable.borrow().get(&atom_id).cloned();
+            if let Some(cleanup) = mount.and_then(|mount| mount(&store)) {
+                self.mounts.borrow_mut().insert(atom_id, cleanup);
+            }
+        }
+    }
+
+    /// Like `sub`, but the callback receives the new value and the previously delivered one
+    pub fn sub_value<T: 'static + Send + Sync, F>(
+        self: Arc<Self>,
+        atom: Arc<impl ReadAtom<T> + ?Sized + 'static>,
+        on_change: F,
+    ) -> impl Fn() + Send + Sync
+    where
+        F: Fn(Arc<T>, Option<Arc<T>>) + 'static + Send + Sync,
+    {
+        let _guard = self.mutex.lock();
+        let prev = Arc::new(Mutex::new(Some(self.clone().get(&*atom))));
+        let store = Arc::downgrade(&self);
+        let atom_c = atom.clone();
+        self.clone().sub(atom, move |_| {
+            let Some(store) = store.upgrade() else {
+                return;
+            };
+            let value = store.get(&*atom_c);
+            let prev = prev.lock().unwrap().replace(value.clone());
+            on_change(value, prev);
+        })
+    }
+
+    /// Yields the current value, then every change
+    pub fn watch<T: 'static + Send + Sync>(
+        self: Arc<Self>,
+        atom: Arc<impl ReadAtom<T> + ?Sized + 'static>,
+    ) -> AtomStream<T> {
+        let _guard = self.mutex.lock();
+        let (sender, receiver) = unbounded();
+        let _ = sender.unbounded_send(self.clone().get(&*atom));
+        let dispose = self.clone().sub_value(atom, move |value, _| {
+            let _ = sender.unbounded_send(value);
+        });
+        AtomStream {
+            receiver,
+            dispose: Box::new(dispose),
+        }
+    }
+
+    pub(crate) fn resolve_async<T: 'static + Send + Sync>(
+        self: Arc<Self>,
         atom_id: Arc<AtomId>,
-        tracked: Rc<RefCell<WeakKeyHashMap<Weak<AtomId>, Box<dyn Fn() -> bool>>>>,
-        getter_id: &usize,
+        getter_id: usize,
+        value: Arc<T>,
+        failed: bool,
     ) {
+        let _guard = self.mutex.lock();
+        // A newer read has started since this future was created, its result wins
+        if self.deps_manager.current_getter_id.borrow().get(&atom_id) != Some(&getter_id) {
+            return;
+        }
+
+        self.set_failed(atom_id.clone(), failed);
+        self.start_batch();
+        self.map.borrow_mut().insert(atom_id.clone(), value);
+        self.mark_changed(atom_id);
+        self.end_batch();
+    }
+
+    fn start_batch(&self) {
+        self.batch.borrow_mut().depth += 1;
+    }
+
+    fn end_batch(&self) {
+        if self.batch.borrow().depth > 1 {
+            self.batch.borrow_mut().depth -= 1;
+            return;
+        }
+        if let Some(history) = self.history.borrow_mut().as_mut() {
+            history.commit();
+        }
+        // Still batching while mounted atoms recompute, so their notifications are queued too
+        loop {
+            let stale = std::mem::take(&mut self.batch.borrow_mut().stale);
+            if stale.is_empty() {
+                break;
+            }
+            if self.deps_manager.run_subs_handlers(stale) {
+                // Recomputed atoms may read different atoms now
+                self.update_mounts();
+            }
+        }
+        let (notify, children) = {
+            let mut batch = self.batch.borrow_mut();
+            batch.depth = 0;
+            (
+                std::mem::take(&mut batch.notify),
+                std::mem::take(&mut batch.children),
+            )
+        };
+        for atom_id in notify {
+            self.notify_subscribers(&atom_id);
+        }
+        for child in children {
+            child.end_batch();
+        }
+    }
+
+    /// Marks dependents stale and queues the atom's subscribers, must be called within a batch
+    fn mark_changed(&self, atom_id: Arc<AtomId>) {
+        let stale = self.deps_manager.propagate_stale(atom_id.clone());
+        self.batch.borrow_mut().stale.extend(stale);
+        self.notify(atom_id.clone());
+        if self.primitives.borrow().contains_key(&atom_id) {
+            self.mark_changed_in_children(atom_id);
+        }
+    }
+
+    /// Atoms computed in children may read this store's primitive atoms, they catch up once this
+    /// batch ends
+    fn mark_changed_in_children(&self, atom_id: Arc<AtomId>) {
+        for child in self.children() {
+            if child.scoped.contains(&atom_id) {
+                continue;
+            }
+            let mut batch = self.batch.borrow_mut();
+            if !batch.children.iter().any(|c| Arc::ptr_eq(c, &child)) {
+                child.start_batch();
+                batch.children.push(child.clone());
+            }
+            drop(batch);
+            let stale = child.deps_manager.propagate_stale(atom_id.clone());
+            child.batch.borrow_mut().stale.extend(stale);
+            child.mark_changed_in_children(atom_id.clone());
+        }
+    }
+
+    fn notify(&self, atom_id: Arc<AtomId>) {
+        let mut batch = self.batch.borrow_mut();
+        if batch.depth > 0 {
+            if !batch.notify.contains(&atom_id) {
+                batch.notify.push(atom_id);
+            }
+            return;
+        }
+        drop(batch);
+        self.notify_subscribers(&atom_id);
+    }
+
+    fn notify_subscribers(&self, atom_id: &AtomId) {
+        let closures = self.subs.borrow().get(atom_id).cloned();
+        if let Some(closures) = closures {
+            self.intercept(StoreEventKind::Notify, atom_id, || closures.notify(&()));
+        }
+    }
+
+    /// Lists every atom this store knows about, sorted by id
+    pub fn inspect(&self) -> Vec<AtomInfo> {
+        let _guard = self.mutex.lock();
+        let mut atom_ids = HashSet::<Arc<AtomId>>::new();
+        atom_ids.extend(self.map.borrow().keys());
+        atom_ids.extend(self.subs.borrow().keys());
+        atom_ids.extend(self.primitives.borrow().keys());
+        atom_ids.extend(self.deps_manager.atom_ids());
+        let mounted = self.deps_manager.mounted();
+        let mut atoms: Vec<_> = atom_ids
+            .into_iter()
+            .map(|atom_id| AtomInfo {
+                id: *atom_id,
+                label: atom_id.label(),
+                value: self.map.borrow().get(&atom_id).cloned(),
+                primitive: self.primitives.borrow().contains_key(&atom_id),
+                mounted: mounted.contains(&atom_id),
+                stale: self.deps_manager.is_stale(&atom_id),
+                subscribers: self.subs.borrow().get(&atom_id).map_or(0, |s| s.len()),
+                dependencies: self.deps_manager.dependencies(&atom_id),
+                dependents: self.deps_manager.dependents(&atom_id),
+            })
+            .collect();
+        atoms.sort_by_key(|a| a.id);
+        atoms
+    }
+
+    pub fn graph_json(&self) -> String {
+        graph_to_json(&self.inspect())
+    }
+
+    /// Graphviz DOT, primitives are boxes, mounted atoms bold and stale atoms red
+    pub fn graph_dot(&self) -> String {
+        graph_to_dot(&self.inspect())
+    }
+
+    pub(crate) fn lock(&self) -> ReentrantMutexGuard<'_, ()> {
+        self.mutex.lock()
+    }
+
+    pub(crate) fn update_deps(&self, atom_id: Arc<AtomId>, tracked: Tracked, getter_id: &usize) {
         return self.deps_manager.update_deps(atom_id, tracked, getter_id);
     }
 
@@ -177,27 +832,47 @@ impl JotaiStore {
         self.map.clone()
     }
 }
-// We trust that with the Reentrant mutex on all public methods, it's Send + Sync
+#[derive(Default)]
+struct Batch {
+    depth: usize,
+    // Mounted atoms to recompute and atoms to notify once the outermost batch ends
+    stale: HashSet<Arc<AtomId>>,
+    notify: Vec<Arc<AtomId>>,
+    // Children that got changes of this store's atoms, they end their batch after this one
+    children: Vec<Arc<JotaiStore>>,
+}
+
+impl Drop for JotaiStore {
+    // Unmounts everything, so a dropped child doesn't keep atoms mounted in its parent
+    fn drop(&mut self) {
+        let mounts: Vec<_> = self.mounts.borrow_mut().drain().collect();
+        for (_, cleanup) in mounts {
+            cleanup();
+        }
+    }
+}
+
+// All state is behind Rc<RefCell<..>>, but it's only ever touched while holding the reentrant
+// mutex, which every public method (and every closure handed out) takes for its whole duration.
+// Subscribers are notified while the lock is held, so they must not block on another thread that
+// uses the same store.
 unsafe impl Send for JotaiStore {}
 unsafe impl Sync for JotaiStore {}
 
 struct DepsManager {
     pub current_getter_id: Rc<RefCell<WeakKeyHashMap<Weak<AtomId>, usize>>>, // Map<AtomKey, GetterId>
-    stale_dep_check: Rc<
-        RefCell<
-            WeakKeyHashMap<
-                Weak<AtomId>,
-                Rc<RefCell<WeakKeyHashMap<Weak<AtomId>, Box<dyn Fn() -> bool>>>>,
-            >,
-        >,
-    >,
+    stale_dep_check: Rc<RefCell<WeakKeyHashMap<Weak<AtomId>, Tracked>>>,
     rev_deps: Rc<RefCell<WeakKeyHashMap<Weak<AtomId>, WeakHashSet<Weak<AtomId>>>>>,
     // stale_atoms is only necessary for derived atoms
     stale_atoms: Rc<RefCell<WeakKeyHashMap<Weak<AtomId>, WeakHashSet<Weak<AtomId>>>>>,
-    subs_handlers: Rc<RefCell<WeakKeyHashMap<Weak<AtomId>, Box<dyn Fn()>>>>,
+    subs_handlers: Rc<RefCell<WeakKeyHashMap<Weak<AtomId>, Arc<dyn Fn() + Send + Sync>>>>,
+    // Atoms to recompute on their next read regardless of their dependencies, e.g. to retry
+    invalidated: Rc<RefCell<WeakHashSet<Weak<AtomId>>>>,
 }
 // Notable Edge cases to handle:
 // 1. async getter, i.e. get, wait a bit, get some more
+//    Handled by AsyncAtom, the getter id is kept so late deps still count, and resolve_async
+//    ignores results from getters that are no longer current
 // 2. A -> B -> BB
 //      ∟> C -> CC
 //         D /
@@ -219,12 +894,13 @@ impl DepsManager {
             rev_deps: Rc::new(RefCell::new(WeakKeyHashMap::new())),
             stale_atoms: Rc::new(RefCell::new(WeakKeyHashMap::new())),
             subs_handlers: Rc::new(RefCell::new(WeakKeyHashMap::new())),
+            invalidated: Rc::new(RefCell::new(WeakHashSet::new())),
         }
     }
 
     fn clear_rev_deps(&self, atom_id: AtomId) {
         if let Some(deps) = self.stale_dep_check.borrow().get(&atom_id) {
-            for dep_key in deps.borrow().keys() {
+            for dep_key in deps.lock().unwrap().keys() {
                 if let Some(rev) = self.rev_deps.borrow_mut().get_mut(&dep_key) {
                     rev.remove(&atom_id);
                 }
@@ -232,12 +908,7 @@ impl DepsManager {
         }
     }
 
-    fn update_deps(
-        &self,
-        atom_id: Arc<AtomId>,
-        tracked: Rc<RefCell<WeakKeyHashMap<Weak<AtomId>, Box<dyn Fn() -> bool>>>>,
-        getter_id: &usize,
-    ) {
+    fn update_deps(&self, atom_id: Arc<AtomId>, tracked: Tracked, getter_id: &usize) {
         if self.current_getter_id.borrow().get(&atom_id) != Some(&getter_id) {
             return;
         }
@@ -245,7 +916,7 @@ impl DepsManager {
         self.stale_dep_check
             .borrow_mut()
             .insert(atom_id.clone(), tracked.clone());
-        for t_key in tracked.borrow().keys() {
+        for t_key in tracked.lock().unwrap().keys() {
             self.rev_deps
                 .borrow_mut()
                 .entry(t_key)
@@ -254,32 +925,85 @@ impl DepsManager {
         }
     }
 
-    fn propagate_stale(&self, atom_id: Arc<AtomId>) {
+    fn propagate_stale(&self, atom_id: Arc<AtomId>) -> HashSet<Arc<AtomId>> {
         let mut seen_atoms = HashSet::<Arc<AtomId>>::new();
         seen_atoms.insert(atom_id.clone());
         let mut stack = vec![atom_id.clone()];
         while let Some(current) = stack.pop() {
             if let Some(dependents) = self.rev_deps.borrow().get(&current) {
                 for dep in dependents {
-                    if seen_atoms.contains(&dep) {
-                        continue;
-                    }
-                    seen_atoms.insert(dep.clone());
+                    // Recorded for every path, in a diamond the dependent has to check both sides
+                    // even if it was already reached through the other one
                     self.stale_atoms
                         .borrow_mut()
                         .entry(dep.clone())
                         .or_insert_with(|| WeakHashSet::new())
                         .insert(current.clone());
-                    stack.push(dep.clone());
+                    if seen_atoms.insert(dep.clone()) {
+                        stack.push(dep);
+                    }
                 }
             }
         }
-        for seen_atom_id in seen_atoms {
-            self.subs_handlers.borrow().get(&seen_atom_id).map(|f| f());
+        seen_atoms
+    }
+
+    /// Returns whether any mounted atom was recomputed
+    fn run_subs_handlers(&self, atom_ids: HashSet<Arc<AtomId>>) -> bool {
+        let mut recomputed = false;
+        for atom_id in self.topological_order(atom_ids) {
+            let handler = self.subs_handlers.borrow().get(&atom_id).cloned();
+            if let Some(handler) = handler {
+                handler();
+                recomputed = true;
+            }
+        }
+        recomputed
+    }
+
+    /// Orders the atoms by dependency depth, so every atom comes after the ones it depends on and
+    /// mounted atoms recompute once, from up to date dependencies
+    fn topological_order(&self, atom_ids: HashSet<Arc<AtomId>>) -> Vec<Arc<AtomId>> {
+        let rev_deps = self.rev_deps.borrow();
+        let dependents = |atom_id: &AtomId| -> Vec<Arc<AtomId>> {
+            rev_deps
+                .get(atom_id)
+                .map(|deps| deps.iter().filter(|d| atom_ids.contains(d)).collect())
+                .unwrap_or_default()
+        };
+        let mut in_degree: HashMap<Arc<AtomId>, usize> = atom_ids
+            .iter()
+            .map(|atom_id| (atom_id.clone(), 0))
+            .collect();
+        for atom_id in &atom_ids {
+            for dep in dependents(atom_id) {
+                *in_degree.entry(dep).or_default() += 1;
+            }
         }
+        let mut ready: VecDeque<_> = in_degree
+            .iter()
+            .filter(|(_, count)| **count == 0)
+            .map(|(atom_id, _)| atom_id.clone())
+            .collect();
+        let mut order = Vec::with_capacity(atom_ids.len());
+        while let Some(current) = ready.pop_front() {
+            for dep in dependents(&current) {
+                let count = in_degree.entry(dep.clone()).or_default();
+                *count -= 1;
+                if *count == 0 {
+                    ready.push_back(dep);
+                }
+            }
+            order.push(current);
+        }
+        order
     }
 
     fn check_stale(&self, atom_id: &AtomId) -> bool {
+        if self.invalidated.borrow_mut().remove(atom_id) {
+            self.stale_atoms.borrow_mut().remove(atom_id);
+            return true;
+        }
         let Some(stale_deps) = self.stale_atoms.borrow_mut().remove(atom_id) else {
             return false;
         };
@@ -290,25 +1014,74 @@ impl DepsManager {
                 .borrow()
                 .get(&atom_id)
                 .map(|v| v.clone());
-            if v.and_then(|deps_map| deps_map.borrow().get(&dep).map(|v| v()))
-                .is_some_and(|v| v)
-            {
+            // Clone the check out, it may recompute dependencies which also track
+            let check = v.and_then(|deps_map| deps_map.lock().unwrap().get(&dep).cloned());
+            if check.is_some_and(|v| v()) {
                 return true;
             }
         }
         false
     }
 
-    fn add_sub<F: Fn() + 'static + Send + Sync>(
-        &self,
-        atom_id: Arc<AtomId>,
-        on_stale: F,
-    ) -> Box<dyn FnOnce()> {
-        let mut handlers = self.subs_handlers.borrow_mut();
-        handlers.insert(atom_id.clone(), Box::new(on_stale));
-        let handlers_c = self.subs_handlers.clone();
-        Box::new(move || {
-            handlers_c.borrow_mut().remove(&atom_id);
-        })
+    fn atom_ids(&self) -> Vec<Arc<AtomId>> {
+        let mut atom_ids: Vec<_> = self.stale_dep_check.borrow().keys().collect();
+        atom_ids.extend(self.rev_deps.borrow().keys());
+        atom_ids
+    }
+
+    fn dependencies(&self, atom_id: &AtomId) -> Vec<AtomId> {
+        let mut deps: Vec<_> = self
+            .stale_dep_check
+            .borrow()
+            .get(atom_id)
+            .map(|deps| deps.lock().unwrap().keys().map(|id| *id).collect())
+            .unwrap_or_default();
+        deps.sort();
+        deps
+    }
+
+    fn dependents(&self, atom_id: &AtomId) -> Vec<AtomId> {
+        let mut deps: Vec<_> = self
+            .rev_deps
+            .borrow()
+            .get(atom_id)
+            .map(|deps| deps.iter().map(|id| *id).collect())
+            .unwrap_or_default();
+        deps.sort();
+        deps
+    }
+
+    fn is_stale(&self, atom_id: &AtomId) -> bool {
+        self.stale_atoms.borrow().contains_key(atom_id)
+            || self.invalidated.borrow().contains(atom_id)
+    }
+
+    fn invalidate(&self, atom_id: Arc<AtomId>) {
+        self.invalidated.borrow_mut().insert(atom_id);
+    }
+
+    /// Subscribed atoms and everything they depend on
+    fn mounted(&self) -> HashSet<Arc<AtomId>> {
+        let mut mounted = HashSet::new();
+        let mut stack: Vec<_> = self.subs_handlers.borrow().keys().collect();
+        while let Some(current) = stack.pop() {
+            if !mounted.insert(current.clone()) {
+                continue;
+            }
+            if let Some(deps) = self.stale_dep_check.borrow().get(&current) {
+                stack.extend(deps.lock().unwrap().keys());
+            }
+        }
+        mounted
+    }
+
+    fn add_sub<F: Fn() + 'static + Send + Sync>(&self, atom_id: Arc<AtomId>, on_stale: F) {
+        self.subs_handlers
+            .borrow_mut()
+            .insert(atom_id, Arc::new(on_stale));
+    }
+
+    fn remove_sub(&self, atom_id: &AtomId) {
+        self.subs_handlers.borrow_mut().remove(atom_id);
     }
 }
@@ -1,36 +1,136 @@
+mod async_atom;
 mod atom_base;
+mod atom_family;
 mod dispatch_atom;
 mod getter_setter;
+mod history;
+mod inspect;
+mod intercept;
 mod jotai_store;
 mod primitive_atom;
+mod reducer_atom;
+mod result_atom;
 mod select_atom;
+mod split_atom;
 mod subscription_set;
+mod watch;
+mod writable_atom;
 
+use std::future::Future;
+use std::hash::Hash;
 use std::sync::Arc;
 
+pub use async_atom::*;
+pub use atom_base::{Atom, AtomId, Equals, ReadAtom, WriteAtom};
+pub use atom_family::*;
 pub use dispatch_atom::*;
 pub use getter_setter::*;
+pub use history::Snapshot;
+pub use inspect::AtomInfo;
+pub use intercept::{StoreEvent, StoreEventKind};
 pub use jotai_store::*;
 pub use primitive_atom::*;
+pub use reducer_atom::*;
+pub use result_atom::*;
 pub use select_atom::*;
+pub use split_atom::*;
+pub use watch::AtomStream;
+pub use writable_atom::*;
 
-pub fn atom<T: Clone + Send + Sync + 'static>(default_value: T) -> PrimitiveAtom<T> {
+pub fn atom<T: Clone + PartialEq + Send + Sync + 'static>(default_value: T) -> PrimitiveAtom<T> {
     PrimitiveAtom::new(default_value)
 }
-pub fn select_atom<T: 'static>(
+pub fn atom_family<K: Hash + Eq, A>(
+    f: impl Fn(&K) -> A + 'static + Send + Sync,
+) -> AtomFamily<K, A> {
+    AtomFamily::new(f)
+}
+pub fn atom_with_reducer<T, Action>(
+    initial: T,
+    reducer: impl Fn(&T, &Action) -> T + 'static + Send + Sync,
+) -> ReducerAtom<T, Action>
+where
+    T: Clone + PartialEq + Send + Sync + 'static,
+    Action: 'static,
+{
+    ReducerAtom::new(initial, reducer)
+}
+pub fn result_atom<T: PartialEq + 'static, E: PartialEq + 'static>(
+    f: impl Fn(&mut Getter) -> Result<T, E> + 'static + Send + Sync,
+) -> ResultAtom<T, E> {
+    ResultAtom::new(f)
+}
+pub fn select_atom<T: PartialEq + 'static>(
     f: impl Fn(&mut Getter) -> T + 'static + Send + Sync,
 ) -> SelectAtom<T> {
     SelectAtom::new(f)
 }
+pub fn split_atom<T: Clone + PartialEq + Send + Sync + 'static>(
+    list: Arc<PrimitiveAtom<Vec<T>>>,
+) -> SplitAtom<T, usize> {
+    SplitAtom::new(list)
+}
+pub fn split_atom_with_key<T, K>(
+    list: Arc<PrimitiveAtom<Vec<T>>>,
+    key: impl Fn(&T) -> K + 'static + Send + Sync,
+) -> SplitAtom<T, K>
+where
+    T: Clone + PartialEq + Send + Sync + 'static,
+    K: Hash + Eq + Clone + Send + Sync + 'static,
+{
+    SplitAtom::new_with_key(list, key)
+}
+pub fn async_atom<T, E, F, Fut>(f: F) -> AsyncAtom<T, E>
+where
+    T: PartialEq + Send + Sync + 'static,
+    E: PartialEq + Send + Sync + 'static,
+    F: Fn(Getter) -> Fut + 'static + Send + Sync,
+    Fut: Future<Output = Result<T, E>> + 'static + Send,
+{
+    AsyncAtom::new(f)
+}
+pub fn writable_atom<T: PartialEq + 'static, Arg: 'static>(
+    read: impl Fn(&mut Getter) -> T + 'static + Send + Sync,
+    write: impl Fn(&mut Setter, Arc<Arg>) + 'static + Send + Sync,
+) -> WritableAtom<T, Arg> {
+    WritableAtom::new(read, write)
+}
+/// A field of `parent` as its own atom. Writes store an updated parent, subscribers only hear
+/// about changes to the field.
+pub fn focus_atom<T, F>(
+    parent: Arc<PrimitiveAtom<T>>,
+    get_field: impl Fn(&T) -> F + 'static + Send + Sync,
+    set_field: impl Fn(&T, &F) -> T + 'static + Send + Sync,
+) -> WritableAtom<F, F>
+where
+    T: Send + Sync + 'static,
+    F: PartialEq + 'static,
+{
+    let read_parent = parent.clone();
+    WritableAtom::new(
+        move |getter| get_field(&getter.get(read_parent.clone())),
+        move |setter, field| {
+            let value = set_field(&setter.get(parent.clone()), &field);
+            setter.set_primitive(&parent, Arc::new(value));
+        },
+    )
+}
 pub fn dispatch_atom<Arg, F>(f: F) -> DispatchAtom<Arg>
 where
-    F: Fn(&mut Setter, Arc<Arg>) + 'static,
+    F: Fn(&mut Setter, Arc<Arg>) + 'static + Send + Sync,
 {
     DispatchAtom::new(f)
 }
+pub fn async_dispatch_atom<Arg, F, Fut>(f: F) -> AsyncDispatchAtom<Arg>
+where
+    F: Fn(Setter, Arc<Arg>) -> Fut + 'static + Send + Sync,
+    Fut: Future<Output = ()> + 'static + Send,
+{
+    AsyncDispatchAtom::new(f)
+}
 pub fn dispatch_with_return_atom<Arg, Return, F>(f: F) -> DispatchWithReturnAtom<Arg, Return>
 where
-    F: Fn(&mut Setter, Arc<Arg>) -> Return + 'static,
+    F: Fn(&mut Setter, Arc<Arg>) -> Return + 'static + Send + Sync,
 {
     DispatchWithReturnAtom::new(f)
 }
@@ -156,6 +256,679 @@ mod tests {
         assert_eq!(*store.clone().get(&*counter_2_atom), 13);
     }
 
+    #[test]
+    fn test_async_dispatch_atom() {
+        use futures::channel::oneshot;
+        use futures::task::noop_waker_ref;
+        use futures::FutureExt;
+        use std::task::{Context, Poll};
+
+        let store = JotaiStore::new();
+        let count_atom = Arc::new(atom(0));
+        let pending_atom = Arc::new(atom(0));
+        let (send_a, receive_a) = oneshot::channel::<i32>();
+        let (send_b, receive_b) = oneshot::channel::<i32>();
+        let responses = Arc::new(Mutex::new(vec![receive_a, receive_b]));
+        let fetch_atom = async_dispatch_atom({
+            let count_atom = count_atom.clone();
+            let pending_atom = pending_atom.clone();
+            move |setter, _: Arc<()>| {
+                let response = responses.lock().unwrap().remove(0);
+                let count_atom = count_atom.clone();
+                let pending_atom = pending_atom.clone();
+                async move {
+                    setter.batch(|setter| {
+                        let pending = *setter.get(pending_atom.clone());
+                        setter.set_primitive(&pending_atom, Arc::new(pending + 1));
+                    });
+                    let amount = response.await.unwrap();
+                    setter.batch(|setter| {
+                        let count = *setter.get(count_atom.clone());
+                        setter.set_primitive(&count_atom, Arc::new(count + amount));
+                        let pending = *setter.get(pending_atom.clone());
+                        setter.set_primitive(&pending_atom, Arc::new(pending - 1));
+                    });
+                }
+            }
+        });
+        let mut cx = Context::from_waker(noop_waker_ref());
+        let mut fetch_a = store.clone().set_async(&fetch_atom, Arc::new(())).boxed();
+        let mut fetch_b = store.clone().set_async(&fetch_atom, Arc::new(())).boxed();
+        assert!(fetch_a.poll_unpin(&mut cx).is_pending());
+        assert!(fetch_b.poll_unpin(&mut cx).is_pending());
+        assert_eq!(*store.clone().get(&*pending_atom), 2);
+
+        // Writes land while both dispatches are waiting, and they finish out of order
+        store.clone().set_primitive(&count_atom, Arc::new(10));
+        send_b.send(2).unwrap();
+        assert_eq!(fetch_b.poll_unpin(&mut cx), Poll::Ready(()));
+        assert_eq!(*store.clone().get(&*count_atom), 12);
+        assert_eq!(*store.clone().get(&*pending_atom), 1);
+        send_a.send(1).unwrap();
+        assert_eq!(fetch_a.poll_unpin(&mut cx), Poll::Ready(()));
+        assert_eq!(*store.clone().get(&*count_atom), 13);
+        assert_eq!(*store.clone().get(&*pending_atom), 0);
+    }
+
+    #[test]
+    fn test_interceptors() {
+        let store = JotaiStore::new();
+        let count_atom = Arc::new(atom(0).with_label("count"));
+        let double_atom = Arc::new(
+            select_atom({
+                let count_atom = count_atom.clone();
+                move |getter| *getter.get(count_atom.clone()) * 2
+            })
+            .with_label("double"),
+        );
+        let increment_atom = dispatch_atom({
+            let count_atom = count_atom.clone();
+            move |setter, _: Arc<()>| {
+                let count = *setter.get(count_atom.clone());
+                setter.set_primitive(&count_atom, Arc::new(count + 1));
+            }
+        })
+        .with_label("increment");
+        let _dispose = store.clone().sub(double_atom.clone(), |_| {});
+        let events = Arc::new(Mutex::new(vec![]));
+        let remove = store.add_interceptor({
+            let events = events.clone();
+            move |event| {
+                let label = event.label.clone().unwrap_or_default();
+                events.lock().unwrap().push((event.kind, label));
+            }
+        });
+        // Invariants can be checked on every event
+        let remove_check = store.add_interceptor({
+            let (store, double_atom) = (Arc::downgrade(&store), double_atom.clone());
+            move |event| {
+                if event.kind == StoreEventKind::Notify {
+                    assert_eq!(*store.upgrade().unwrap().get(&*double_atom) % 2, 0);
+                }
+            }
+        });
+
+        store.clone().set(&increment_atom, Arc::new(()));
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                (StoreEventKind::Set, "count".to_string()),
+                (StoreEventKind::Recompute, "double".to_string()),
+                (StoreEventKind::Notify, "double".to_string()),
+                (StoreEventKind::Dispatch, "increment".to_string()),
+            ]
+        );
+
+        remove();
+        remove_check();
+        store.clone().set(&increment_atom, Arc::new(()));
+        assert_eq!(events.lock().unwrap().len(), 4);
+
+        // Dropping the remover keeps the interceptor
+        drop(store.add_interceptor({
+            let events = events.clone();
+            move |event| events.lock().unwrap().push((event.kind, String::new()))
+        }));
+        store.clone().set(&increment_atom, Arc::new(()));
+        assert_eq!(events.lock().unwrap().len(), 8);
+    }
+
+    #[test]
+    fn test_batched_writes() {
+        let store = JotaiStore::new();
+        let counter_atom = Arc::new(atom(10));
+        let counter_2_atom = Arc::new(atom(10));
+        let sum_atom = Arc::new(select_atom({
+            let counter_atom = counter_atom.clone();
+            let counter_2_atom = counter_2_atom.clone();
+            move |getter| *getter.get(counter_atom.clone()) + *getter.get(counter_2_atom.clone())
+        }));
+        let seen_sums = Arc::new(Mutex::new(vec![]));
+        let _dispose = store.clone().sub(sum_atom.clone(), {
+            let store = store.clone();
+            let sum_atom = sum_atom.clone();
+            let seen_sums = seen_sums.clone();
+            move |_| {
+                let sum = *store.clone().get(&*sum_atom);
+                seen_sums.lock().unwrap().push(sum);
+            }
+        });
+        let counter_notified = Arc::new(Mutex::new(0));
+        let _dispose_counter = store.clone().sub(counter_atom.clone(), {
+            let counter = counter_notified.clone();
+            move |_| *counter.lock().unwrap() += 1
+        });
+        let set_counters_atom = dispatch_atom({
+            let counter_atom = counter_atom.clone();
+            let counter_2_atom = counter_2_atom.clone();
+            move |setter, arg: Arc<i32>| {
+                setter.set_primitive(&counter_atom, arg.clone());
+                setter.set_primitive(&counter_2_atom, arg.clone());
+            }
+        });
+
+        store.clone().set(&set_counters_atom, Arc::new(13));
+        assert_eq!(*seen_sums.lock().unwrap(), vec![26]);
+
+        store.clone().batch(|setter| {
+            setter.set_primitive(&counter_atom, Arc::new(1));
+            setter.set_primitive(&counter_atom, Arc::new(2));
+            // reads inside the batch see the writes so far
+            assert_eq!(*setter.get(sum_atom.clone()), 15);
+            setter.set(&set_counters_atom, Arc::new(5));
+            assert_eq!(*seen_sums.lock().unwrap(), vec![26]);
+        });
+        assert_eq!(*seen_sums.lock().unwrap(), vec![26, 10]);
+        assert_eq!(*counter_notified.lock().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_sub_value_atom() {
+        let store = JotaiStore::new();
+        let value_atom = Arc::new(atom(10));
+        let derivative_atom = Arc::new(select_atom({
+            let value_atom = value_atom.clone();
+            move |getter| *getter.get(value_atom.clone()) / 2
+        }));
+        let seen = Arc::new(Mutex::new(vec![]));
+        let dispose = store.clone().sub_value(derivative_atom.clone(), {
+            let seen = seen.clone();
+            move |value, prev| seen.lock().unwrap().push((*value, prev.map(|v| *v)))
+        });
+        store.clone().set_primitive(&value_atom, Arc::new(11));
+        store.clone().set_primitive(&value_atom, Arc::new(12));
+        store.clone().set_primitive(&value_atom, Arc::new(14));
+        assert_eq!(*seen.lock().unwrap(), vec![(6, Some(5)), (7, Some(6))]);
+
+        dispose();
+        store.clone().set_primitive(&value_atom, Arc::new(16));
+        assert_eq!(seen.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_on_mount_atom() {
+        let store = JotaiStore::new();
+        let mounted = Arc::new(Mutex::new(0));
+        let unmounted = Arc::new(Mutex::new(0));
+        let value_atom = Arc::new(atom(0).with_on_mount({
+            let mounted = mounted.clone();
+            let unmounted = unmounted.clone();
+            move |set_self| {
+                *mounted.lock().unwrap() += 1;
+                set_self(Arc::new(42));
+                let unmounted = unmounted.clone();
+                move || *unmounted.lock().unwrap() += 1
+            }
+        }));
+        assert_eq!(*store.clone().get(&*value_atom), 0);
+        assert_eq!(*mounted.lock().unwrap(), 0);
+
+        let sub_counter = Arc::new(Mutex::new(0));
+        let dispose = store.clone().sub(value_atom.clone(), {
+            let counter = sub_counter.clone();
+            move |_| *counter.lock().unwrap() += 1
+        });
+        let dispose_2 = store.clone().sub(value_atom.clone(), |_| {});
+        assert_eq!(*mounted.lock().unwrap(), 1);
+        assert_eq!(*sub_counter.lock().unwrap(), 1);
+        assert_eq!(*store.clone().get(&*value_atom), 42);
+
+        dispose();
+        assert_eq!(*unmounted.lock().unwrap(), 0);
+        dispose_2();
+        assert_eq!(*unmounted.lock().unwrap(), 1);
+
+        let _dispose = store.clone().sub(value_atom.clone(), |_| {});
+        assert_eq!(*mounted.lock().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_on_mount_through_dependent() {
+        let store = JotaiStore::new();
+        let mounted = Arc::new(Mutex::new(0));
+        let unmounted = Arc::new(Mutex::new(0));
+        let value_atom = Arc::new(atom(1).with_on_mount({
+            let mounted = mounted.clone();
+            let unmounted = unmounted.clone();
+            move |set_self| {
+                *mounted.lock().unwrap() += 1;
+                set_self(Arc::new(2));
+                let unmounted = unmounted.clone();
+                move || *unmounted.lock().unwrap() += 1
+            }
+        }));
+        let enabled_atom = Arc::new(atom(true));
+        let derived_atom = Arc::new(select_atom({
+            let (value_atom, enabled_atom) = (value_atom.clone(), enabled_atom.clone());
+            move |getter| match *getter.get(enabled_atom.clone()) {
+                true => *getter.get(value_atom.clone()) * 10,
+                false => 0,
+            }
+        }));
+
+        let dispose = store.clone().sub(derived_atom.clone(), |_| {});
+        assert_eq!(*mounted.lock().unwrap(), 1);
+        assert_eq!(*store.clone().get(&*derived_atom), 20);
+
+        // Unmounted once the dependent stops reading it, and mounted again when it reads it again
+        store.clone().set_primitive(&enabled_atom, Arc::new(false));
+        assert_eq!(*unmounted.lock().unwrap(), 1);
+        store.clone().set_primitive(&enabled_atom, Arc::new(true));
+        assert_eq!(*mounted.lock().unwrap(), 2);
+
+        dispose();
+        assert_eq!(*unmounted.lock().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_custom_equality() {
+        struct User {
+            id: u32,
+            name: String,
+        }
+        let store = JotaiStore::new();
+        let name_atom = Arc::new(atom("a".to_string()));
+        let user_atom = Arc::new(SelectAtom::new_with_equals(
+            {
+                let name_atom = name_atom.clone();
+                move |getter| User {
+                    id: 1,
+                    name: (*getter.get(name_atom.clone())).clone(),
+                }
+            },
+            Arc::new(|a: &User, b: &User| a.id == b.id),
+        ));
+        let tick_atom = Arc::new(atom(()).with_equals(|_, _| false));
+        let user_counter = Arc::new(Mutex::new(0));
+        let tick_counter = Arc::new(Mutex::new(0));
+        let _dispose_user = store.clone().sub(user_atom.clone(), {
+            let counter = user_counter.clone();
+            move |_| *counter.lock().unwrap() += 1
+        });
+        let _dispose_tick = store.clone().sub(tick_atom.clone(), {
+            let counter = tick_counter.clone();
+            move |_| *counter.lock().unwrap() += 1
+        });
+
+        // Same id, so it's considered unchanged
+        store
+            .clone()
+            .set_primitive(&name_atom, Arc::new("b".to_string()));
+        assert_eq!(*user_counter.lock().unwrap(), 0);
+        assert_eq!(store.clone().get(&*user_atom).name, "b");
+
+        // Always notifies, even though the value is the same
+        store.clone().set_primitive(&tick_atom, Arc::new(()));
+        store.clone().set_primitive(&tick_atom, Arc::new(()));
+        assert_eq!(*tick_counter.lock().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_diamond_dependencies() {
+        let store = JotaiStore::new();
+        let reads = Arc::new(Mutex::new(vec![]));
+        let counted = |name: &'static str| {
+            let reads = reads.clone();
+            move || reads.lock().unwrap().push(name)
+        };
+        let a_atom = Arc::new(atom(1));
+        let b_atom = Arc::new(select_atom({
+            let (a_atom, read) = (a_atom.clone(), counted("b"));
+            move |getter| {
+                read();
+                *getter.get(a_atom.clone()) * 2
+            }
+        }));
+        // Doesn't change for small values, so d is only stale through b
+        let c_atom = Arc::new(select_atom({
+            let (a_atom, read) = (a_atom.clone(), counted("c"));
+            move |getter| {
+                read();
+                *getter.get(a_atom.clone()) > 100
+            }
+        }));
+        let d_atom = Arc::new(select_atom({
+            let (b_atom, c_atom, read) = (b_atom.clone(), c_atom.clone(), counted("d"));
+            move |getter| {
+                read();
+                (*getter.get(b_atom.clone()), *getter.get(c_atom.clone()))
+            }
+        }));
+        let seen = Arc::new(Mutex::new(vec![]));
+        let _dispose_b = store.clone().sub(b_atom.clone(), |_| {});
+        let _dispose_c = store.clone().sub(c_atom.clone(), |_| {});
+        let _dispose_d = store.clone().sub_value(d_atom.clone(), {
+            let seen = seen.clone();
+            let (store, b_atom) = (Arc::downgrade(&store), b_atom.clone());
+            move |value, _| {
+                let b = *store.upgrade().unwrap().get(&*b_atom);
+                seen.lock().unwrap().push((*value, b));
+            }
+        });
+
+        for value in 2..6 {
+            reads.lock().unwrap().clear();
+            store.clone().set_primitive(&a_atom, Arc::new(value));
+            let mut reads = reads.lock().unwrap().clone();
+            reads.sort();
+            assert_eq!(reads, vec!["b", "c", "d"]);
+        }
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![
+                ((4, false), 4),
+                ((6, false), 6),
+                ((8, false), 8),
+                ((10, false), 10)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_deep_chain() {
+        let store = JotaiStore::new();
+        let reads = Arc::new(Mutex::new(0));
+        let root_atom = Arc::new(atom(0));
+        let mut chain: Vec<Arc<dyn ReadAtom<i32>>> = vec![root_atom.clone()];
+        for _ in 0..50 {
+            let prev = chain.last().unwrap().clone();
+            let reads = reads.clone();
+            chain.push(Arc::new(select_atom(move |getter| {
+                *reads.lock().unwrap() += 1;
+                *getter.get(prev.clone()) + 1
+            })));
+        }
+        let notified = Arc::new(Mutex::new(vec![]));
+        let disposers: Vec<_> = chain
+            .iter()
+            .enumerate()
+            .map(|(i, atom)| {
+                let notified = notified.clone();
+                let (store_c, atom_c) = (Arc::downgrade(&store), atom.clone());
+                store.clone().sub(atom.clone(), move |_| {
+                    let value = *store_c.upgrade().unwrap().get(&*atom_c);
+                    notified.lock().unwrap().push((i, value));
+                })
+            })
+            .collect();
+        assert_eq!(*reads.lock().unwrap(), 50);
+
+        *reads.lock().unwrap() = 0;
+        store.clone().set_primitive(&root_atom, Arc::new(100));
+        assert_eq!(*reads.lock().unwrap(), 50);
+        let notified = notified.lock().unwrap();
+        assert_eq!(notified.len(), 51);
+        assert!(notified.iter().all(|(i, value)| *value == 100 + *i as i32));
+        drop(disposers);
+    }
+
+    #[test]
+    fn test_reset_atom() {
+        let store = JotaiStore::new();
+        let value_atom = Arc::new(atom(10));
+        let double_atom = Arc::new(select_atom({
+            let value_atom = value_atom.clone();
+            move |getter| *getter.get(value_atom.clone()) * 2
+        }));
+        let counter = Arc::new(Mutex::new(0));
+        let _dispose = store.clone().sub(double_atom.clone(), {
+            let counter = counter.clone();
+            move |_| *counter.lock().unwrap() += 1
+        });
+
+        store.clone().set_primitive(&value_atom, Arc::new(5));
+        assert_eq!(*store.clone().get(&*double_atom), 10);
+        store.clone().reset(&value_atom);
+        assert_eq!(*store.clone().get(&*value_atom), 10);
+        assert_eq!(*store.clone().get(&*double_atom), 20);
+        assert_eq!(*counter.lock().unwrap(), 2);
+
+        // Already at its default
+        store.clone().reset(&value_atom);
+        assert_eq!(*counter.lock().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_refresh_atom() {
+        let store = JotaiStore::new();
+        let source = Arc::new(Mutex::new(1));
+        let read_counter = Arc::new(Mutex::new(0));
+        let external_atom = Arc::new(select_atom({
+            let source = source.clone();
+            let read_counter = read_counter.clone();
+            move |_| {
+                *read_counter.lock().unwrap() += 1;
+                *source.lock().unwrap()
+            }
+        }));
+        let label_atom = Arc::new(select_atom({
+            let external_atom = external_atom.clone();
+            move |getter| format!("value: {}", getter.get(external_atom.clone()))
+        }));
+        let labels = Arc::new(Mutex::new(vec![]));
+        let _dispose = store.clone().sub_value(label_atom.clone(), {
+            let labels = labels.clone();
+            move |value, _| labels.lock().unwrap().push((*value).clone())
+        });
+        assert_eq!(*read_counter.lock().unwrap(), 1);
+
+        *source.lock().unwrap() = 2;
+        assert_eq!(*store.clone().get(&*label_atom), "value: 1");
+        store.clone().refresh(&*external_atom);
+        assert_eq!(*store.clone().get(&*label_atom), "value: 2");
+        assert_eq!(*labels.lock().unwrap(), vec!["value: 2".to_string()]);
+
+        // Recomputed, but unchanged values aren't propagated
+        store.clone().refresh(&*external_atom);
+        assert_eq!(*read_counter.lock().unwrap(), 3);
+        assert_eq!(labels.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_focus_atom() {
+        #[derive(Clone, PartialEq)]
+        struct Settings {
+            name: String,
+            volume: u32,
+        }
+        let store = JotaiStore::new();
+        let settings_atom = Arc::new(atom(Settings {
+            name: "default".to_string(),
+            volume: 5,
+        }));
+        let volume_atom = Arc::new(focus_atom(
+            settings_atom.clone(),
+            |settings| settings.volume,
+            |settings, volume| Settings {
+                volume: *volume,
+                ..settings.clone()
+            },
+        ));
+        let volumes = Arc::new(Mutex::new(vec![]));
+        let _dispose = store.clone().sub_value(volume_atom.clone(), {
+            let volumes = volumes.clone();
+            move |volume, _| volumes.lock().unwrap().push(*volume)
+        });
+
+        store.clone().set(&*volume_atom, Arc::new(8));
+        assert_eq!(store.clone().get(&*settings_atom).volume, 8);
+        assert_eq!(store.clone().get(&*settings_atom).name, "default");
+
+        // Other fields don't notify
+        store.clone().set_primitive(
+            &settings_atom,
+            Arc::new(Settings {
+                name: "renamed".to_string(),
+                volume: 8,
+            }),
+        );
+        assert_eq!(*volumes.lock().unwrap(), vec![8]);
+        store.clone().set(&*volume_atom, Arc::new(3));
+        assert_eq!(store.clone().get(&*settings_atom).name, "renamed");
+        assert_eq!(*volumes.lock().unwrap(), vec![8, 3]);
+    }
+
+    #[test]
+    fn test_scoped_store() {
+        let store = JotaiStore::new();
+        let global_atom = Arc::new(atom(1));
+        let local_atom = Arc::new(atom(10));
+        let sum_atom = Arc::new(select_atom({
+            let global_atom = global_atom.clone();
+            let local_atom = local_atom.clone();
+            move |getter| *getter.get(global_atom.clone()) + *getter.get(local_atom.clone())
+        }));
+        let child = store
+            .clone()
+            .child([local_atom.get_id(), sum_atom.get_id()]);
+        let sum_counter = Arc::new(Mutex::new(0));
+        let global_counter = Arc::new(Mutex::new(0));
+        let dispose_sum = child.clone().sub(sum_atom.clone(), {
+            let counter = sum_counter.clone();
+            move |_| *counter.lock().unwrap() += 1
+        });
+        let dispose_global = child.clone().sub(global_atom.clone(), {
+            let counter = global_counter.clone();
+            move |_| *counter.lock().unwrap() += 1
+        });
+
+        // Scoped atoms stay in the child
+        child.clone().set_primitive(&local_atom, Arc::new(20));
+        assert_eq!(*child.clone().get(&*local_atom), 20);
+        assert_eq!(*store.clone().get(&*local_atom), 10);
+        assert_eq!(*child.clone().get(&*sum_atom), 21);
+        assert_eq!(*store.clone().get(&*sum_atom), 11);
+        assert_eq!(*sum_counter.lock().unwrap(), 1);
+
+        // Everything else is shared with the parent
+        child.clone().set_primitive(&global_atom, Arc::new(2));
+        assert_eq!(*store.clone().get(&*global_atom), 2);
+        store.clone().set_primitive(&global_atom, Arc::new(3));
+        assert_eq!(*child.clone().get(&*sum_atom), 23);
+        assert_eq!(*sum_counter.lock().unwrap(), 3);
+        assert_eq!(*global_counter.lock().unwrap(), 2);
+
+        let global_subscribers = |store: &Arc<JotaiStore>| {
+            store
+                .inspect()
+                .into_iter()
+                .find(|a| a.id == *global_atom.get_id())
+                .map(|a| a.subscribers)
+        };
+        // The child's own subscription and the one mounting it for `sum_atom`
+        assert_eq!(global_subscribers(&store), Some(2));
+        dispose_global();
+        dispose_sum();
+        assert_eq!(global_subscribers(&store), Some(0));
+    }
+
+    #[test]
+    fn test_scoped_store_dependents() {
+        let store = JotaiStore::new();
+        let global_atom = Arc::new(atom(1));
+        let local_atom = Arc::new(atom(1));
+        let times_ten_atom = Arc::new(select_atom({
+            let local_atom = local_atom.clone();
+            move |getter| *getter.get(local_atom.clone()) * 10
+        }));
+        let sum_atom = Arc::new(select_atom({
+            let (global_atom, times_ten_atom) = (global_atom.clone(), times_ten_atom.clone());
+            move |getter| *getter.get(global_atom.clone()) + *getter.get(times_ten_atom.clone())
+        }));
+        let child = store.clone().child([local_atom.get_id()]);
+
+        // Dependents of scoped atoms read the scoped value without being scoped themselves
+        child.clone().set_primitive(&local_atom, Arc::new(5));
+        assert_eq!(*child.clone().get(&*times_ten_atom), 50);
+        assert_eq!(*store.clone().get(&*times_ten_atom), 10);
+        assert_eq!(*child.clone().get(&*sum_atom), 51);
+
+        // Unmounted atoms in the child still see writes to the parent
+        store.clone().set_primitive(&global_atom, Arc::new(2));
+        assert_eq!(*child.clone().get(&*sum_atom), 52);
+
+        let grandchild = child.clone().child([]);
+        let sums = Arc::new(Mutex::new(vec![]));
+        let _dispose = grandchild.clone().sub_value(sum_atom.clone(), {
+            let sums = sums.clone();
+            move |sum, _| sums.lock().unwrap().push(*sum)
+        });
+        store.clone().set_primitive(&global_atom, Arc::new(3));
+        child.clone().set_primitive(&local_atom, Arc::new(6));
+        assert_eq!(*sums.lock().unwrap(), vec![53, 63]);
+    }
+
+    #[test]
+    fn test_concurrent_writes() {
+        let store = JotaiStore::new();
+        let counter_atom = Arc::new(atom(0));
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    for _ in 0..200 {
+                        store.clone().batch(|setter| {
+                            let value = *setter.get(counter_atom.clone());
+                            setter.set_primitive(&counter_atom, Arc::new(value + 1));
+                        });
+                    }
+                });
+            }
+        });
+        assert_eq!(*store.clone().get(&*counter_atom), 1600);
+    }
+
+    #[test]
+    fn test_concurrent_sub_and_get() {
+        let store = JotaiStore::new();
+        let value_atom = Arc::new(atom(0));
+        let pair_atom = Arc::new(select_atom({
+            let value_atom = value_atom.clone();
+            move |getter| {
+                let value = *getter.get(value_atom.clone());
+                (value, value * 2)
+            }
+        }));
+        let sub_counter = Arc::new(Mutex::new(0));
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                for i in 1..=500 {
+                    store.clone().set_primitive(&value_atom, Arc::new(i));
+                }
+            });
+            for _ in 0..3 {
+                scope.spawn(|| {
+                    for _ in 0..500 {
+                        let pair = store.clone().get(&*pair_atom);
+                        assert_eq!(pair.1, pair.0 * 2);
+                    }
+                });
+                scope.spawn(|| {
+                    for _ in 0..100 {
+                        let dispose = store.clone().sub(pair_atom.clone(), {
+                            let store = store.clone();
+                            let pair_atom = pair_atom.clone();
+                            let counter = sub_counter.clone();
+                            move |_| {
+                                let pair = store.clone().get(&*pair_atom);
+                                assert_eq!(pair.1, pair.0 * 2);
+                                *counter.lock().unwrap() += 1;
+                            }
+                        });
+                        dispose();
+                    }
+                });
+            }
+        });
+        assert_eq!(*store.clone().get(&*pair_atom), (500, 1000));
+        let pair = store
+            .inspect()
+            .into_iter()
+            .find(|a| a.id == *pair_atom.get_id())
+            .unwrap();
+        assert_eq!(pair.subscribers, 0);
+        assert!(!pair.mounted);
+    }
+
     #[test]
     fn test_sub_atom() {
         let store = JotaiStore::new();
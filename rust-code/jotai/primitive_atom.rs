@@ -2,11 +2,26 @@ use std::hash::{Hash, Hasher};
 use std::sync::Arc;
 
 use crate::atom_base::*;
-use crate::getter_setter::Getter;
+use crate::getter_setter::{Getter, Setter};
+use crate::jotai_store::JotaiStore;
+
+pub type SetSelf<T> = Arc<dyn Fn(Arc<T>) + Send + Sync>;
+pub(crate) type OnSet<T> = Arc<dyn Fn(&T) + Send + Sync>;
+type Initial<T> = Box<dyn Fn() -> T + Send + Sync>;
+type OnMount<T> = Box<
+    dyn Fn(&Arc<JotaiStore>, Arc<AtomId>, Equals<T>, Option<OnSet<T>>) -> Box<dyn FnOnce() + Send>
+        + Send
+        + Sync,
+>;
 
 pub struct PrimitiveAtom<T> {
     id: Arc<AtomId>,
     read: Box<dyn Fn(&mut Getter) -> T + Send + Sync>,
+    // What `reset` goes back to
+    pub(crate) initial: Option<Initial<T>>,
+    pub(crate) equals: Equals<T>,
+    on_mount: Option<OnMount<T>>,
+    pub(crate) on_set: Option<OnSet<T>>,
 }
 impl<T> PartialEq for PrimitiveAtom<T> {
     fn eq(&self, other: &Self) -> bool {
@@ -19,29 +34,117 @@ impl<T> Hash for PrimitiveAtom<T> {
         self.id.hash(state);
     }
 }
-impl<T: Clone + Send + Sync + 'static> PrimitiveAtom<T> {
+impl<T: Clone + PartialEq + Send + Sync + 'static> PrimitiveAtom<T> {
     pub fn new(default_value: T) -> Self {
-        Self {
-            id: Arc::new(AtomId::new()),
-            read: Box::new(move |_| default_value.clone()),
-        }
+        Self::new_with_equals(default_value, default_equals())
     }
 }
-impl<T: Send + Sync + 'static> PrimitiveAtom<T> {
+impl<T: Clone + Send + Sync + 'static> PrimitiveAtom<T> {
+    /// For values without `PartialEq`, or where comparing them is too expensive
+    pub fn new_with_equals(default_value: T, equals: Equals<T>) -> Self {
+        let initial = default_value.clone();
+        Self::new_fn_with_equals(Box::new(move |_| default_value.clone()), equals)
+            .with_initial(initial)
+    }
+
+    /// Sets the value `reset` goes back to, e.g. for an atom whose read loads a stored value
+    pub fn with_initial(mut self, value: T) -> Self {
+        self.initial = Some(Box::new(move || value.clone()));
+        self
+    }
+}
+impl<T: PartialEq + Send + Sync + 'static> PrimitiveAtom<T> {
     pub fn new_fn(f: Box<dyn Fn(&mut Getter) -> T + Send + Sync>) -> Self {
+        Self::new_fn_with_equals(f, default_equals())
+    }
+}
+impl<T: Send + Sync + 'static> PrimitiveAtom<T> {
+    pub fn new_fn_with_equals(
+        f: Box<dyn Fn(&mut Getter) -> T + Send + Sync>,
+        equals: Equals<T>,
+    ) -> Self {
         Self {
             id: Arc::new(AtomId::new()),
             read: f,
+            initial: None,
+            equals,
+            on_mount: None,
+            on_set: None,
         }
     }
+
+    /// The value `reset` goes back to, if the atom has one
+    pub fn initial(&self) -> Option<T> {
+        self.initial.as_ref().map(|initial| initial())
+    }
+
+    /// Replaces the equality used to skip writes of an unchanged value
+    pub fn with_equals<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&T, &T) -> bool + 'static + Send + Sync,
+    {
+        self.equals = Arc::new(f);
+        self
+    }
+
+    /// Runs `f` when the atom gets its first subscriber, e.g. to start polling or a watcher.
+    /// `f` gets a setter for the atom's own value, the cleanup it returns runs when the last
+    /// subscriber is disposed.
+    pub fn with_on_mount<F, C>(mut self, f: F) -> Self
+    where
+        F: Fn(SetSelf<T>) -> C + 'static + Send + Sync,
+        C: FnOnce() + 'static + Send,
+    {
+        self.on_mount = Some(Box::new(move |store, atom_id, equals, on_set| {
+            let store = Arc::downgrade(store);
+            let atom_id = Arc::downgrade(&atom_id);
+            let set_self: SetSelf<T> = Arc::new(move |value| {
+                if let (Some(store), Some(atom_id)) = (store.upgrade(), atom_id.upgrade()) {
+                    store.set_value(atom_id, value, &*equals, on_set.as_ref());
+                }
+            });
+            Box::new(f(set_self))
+        }));
+        self
+    }
+
+    /// Runs `f` after every write that changes the atom's value, e.g. to persist it
+    pub fn with_on_set<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&T) + 'static + Send + Sync,
+    {
+        self.on_set = Some(Arc::new(f));
+        self
+    }
 }
 impl<T> Atom for PrimitiveAtom<T> {
     fn get_id(&self) -> Arc<AtomId> {
         self.id.clone()
     }
+    fn on_mount(&self, store: &Arc<JotaiStore>) -> Option<Box<dyn FnOnce() + Send>> {
+        self.on_mount.as_ref().map(|f| {
+            f(
+                store,
+                self.id.clone(),
+                self.equals.clone(),
+                self.on_set.clone(),
+            )
+        })
+    }
+    fn is_primitive(&self) -> bool {
+        true
+    }
 }
 impl<T> ReadAtom<T> for PrimitiveAtom<T> {
     fn get_read(&self) -> &Box<dyn Fn(&mut Getter) -> T + Send + Sync> {
         &self.read
     }
+    fn is_equal(&self, a: &T, b: &T) -> bool {
+        (self.equals)(a, b)
+    }
+}
+impl<T: 'static + Send + Sync> WriteAtom<T> for PrimitiveAtom<T> {
+    fn write(&self, setter: &mut Setter, arg: Arc<T>) {
+        setter.set_primitive(self, arg)
+    }
 }
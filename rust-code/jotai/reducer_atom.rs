@@ -0,0 +1,198 @@
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+use crate::atom_base::*;
+use crate::getter_setter::{Getter, Setter};
+
+type Reducer<T, Action> = Box<dyn Fn(&T, &Action) -> T + Send + Sync>;
+
+/// Holds state like a primitive atom, but is only updated by dispatching actions through `set`
+pub struct ReducerAtom<T, Action> {
+    id: Arc<AtomId>,
+    read: Box<dyn Fn(&mut Getter) -> T + Send + Sync>,
+    reducer: Reducer<T, Action>,
+    equals: Equals<T>,
+}
+impl<T, Action> PartialEq for ReducerAtom<T, Action> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+impl<T, Action> Eq for ReducerAtom<T, Action> {}
+impl<T, Action> Hash for ReducerAtom<T, Action> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+impl<T: Clone + PartialEq + Send + Sync + 'static, Action: 'static> ReducerAtom<T, Action> {
+    pub fn new<R>(initial: T, reducer: R) -> Self
+    where
+        R: Fn(&T, &Action) -> T + 'static + Send + Sync,
+    {
+        Self {
+            id: Arc::new(AtomId::new()),
+            read: Box::new(move |_| initial.clone()),
+            reducer: Box::new(reducer),
+            equals: default_equals(),
+        }
+    }
+}
+impl<T, Action> ReducerAtom<T, Action> {
+    /// Replaces the equality used to skip actions that don't change the state
+    pub fn with_equals<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&T, &T) -> bool + 'static + Send + Sync,
+    {
+        self.equals = Arc::new(f);
+        self
+    }
+}
+impl<T, Action> Atom for ReducerAtom<T, Action> {
+    fn get_id(&self) -> Arc<AtomId> {
+        self.id.clone()
+    }
+    fn is_primitive(&self) -> bool {
+        true
+    }
+}
+impl<T, Action> ReadAtom<T> for ReducerAtom<T, Action> {
+    fn get_read(&self) -> &Box<dyn Fn(&mut Getter) -> T + Send + Sync> {
+        &self.read
+    }
+    fn is_equal(&self, a: &T, b: &T) -> bool {
+        (self.equals)(a, b)
+    }
+}
+impl<T: 'static + Send + Sync, Action> WriteAtom<Action> for ReducerAtom<T, Action> {
+    fn write(&self, setter: &mut Setter, action: Arc<Action>) {
+        let current = setter.store.clone().get(self);
+        let next = (self.reducer)(&current, &action);
+        setter
+            .store
+            .set_value(self.get_id(), Arc::new(next), &*self.equals, None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use crate::*;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Todos {
+        items: Vec<String>,
+        done: usize,
+    }
+    enum TodoAction {
+        Add(String),
+        Complete,
+        Clear,
+    }
+    fn todo_reducer(state: &Todos, action: &TodoAction) -> Todos {
+        match action {
+            TodoAction::Add(item) => Todos {
+                items: [state.items.clone(), vec![item.clone()]].concat(),
+                ..state.clone()
+            },
+            TodoAction::Complete => Todos {
+                done: (state.done + 1).min(state.items.len()),
+                ..state.clone()
+            },
+            TodoAction::Clear => Todos {
+                items: vec![],
+                done: 0,
+            },
+        }
+    }
+
+    #[test]
+    fn test_reducer_atom() {
+        let store = JotaiStore::new();
+        let todos_atom = Arc::new(atom_with_reducer(
+            Todos {
+                items: vec![],
+                done: 0,
+            },
+            todo_reducer,
+        ));
+        let left_atom = Arc::new(select_atom({
+            let todos_atom = todos_atom.clone();
+            move |getter| {
+                let todos = getter.get(todos_atom.clone());
+                todos.items.len() - todos.done
+            }
+        }));
+        let sub_counter = Arc::new(Mutex::new(0));
+        let _dispose = store.clone().sub(todos_atom.clone(), {
+            let counter = sub_counter.clone();
+            move |_| *counter.lock().unwrap() += 1
+        });
+
+        store
+            .clone()
+            .set(&*todos_atom, Arc::new(TodoAction::Add("a".to_string())));
+        store
+            .clone()
+            .set(&*todos_atom, Arc::new(TodoAction::Add("b".to_string())));
+        store
+            .clone()
+            .set(&*todos_atom, Arc::new(TodoAction::Complete));
+        assert_eq!(*store.clone().get(&*left_atom), 1);
+        assert_eq!(*sub_counter.lock().unwrap(), 3);
+
+        // Clearing twice only changes the state once
+        store.clone().set(&*todos_atom, Arc::new(TodoAction::Clear));
+        store.clone().set(&*todos_atom, Arc::new(TodoAction::Clear));
+        assert_eq!(*store.clone().get(&*left_atom), 0);
+        assert_eq!(*sub_counter.lock().unwrap(), 4);
+    }
+
+    #[test]
+    fn test_reducer_atom_undo() {
+        let store = JotaiStore::new();
+        let counter_atom = Arc::new(atom_with_reducer(0, |state: &i32, delta: &i32| {
+            state + delta
+        }));
+        let add_twice_atom = dispatch_atom({
+            let counter_atom = counter_atom.clone();
+            move |setter, delta: Arc<i32>| {
+                setter.set(&*counter_atom, delta.clone());
+                setter.set(&*counter_atom, delta);
+            }
+        });
+        store.enable_history(10);
+        store.clone().set(&*counter_atom, Arc::new(1));
+        store.clone().set(&add_twice_atom, Arc::new(2));
+        assert_eq!(*store.clone().get(&*counter_atom), 5);
+
+        assert!(store.undo());
+        assert_eq!(*store.clone().get(&*counter_atom), 1);
+        assert_eq!(
+            store.snapshot().get::<i32>(&*counter_atom),
+            Some(Arc::new(1))
+        );
+    }
+
+    #[test]
+    fn test_reducer_atom_interceptors() {
+        let store = JotaiStore::new();
+        let counter_atom = Arc::new(atom_with_reducer(0, |state: &i32, delta: &i32| {
+            state + delta
+        }));
+        let events = Arc::new(Mutex::new(vec![]));
+        let _remove = store.add_interceptor({
+            let events = events.clone();
+            move |event| events.lock().unwrap().push(event.kind)
+        });
+        store.clone().set(&*counter_atom, Arc::new(1));
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                StoreEventKind::Recompute,
+                StoreEventKind::Set,
+                StoreEventKind::Dispatch
+            ]
+        );
+    }
+}
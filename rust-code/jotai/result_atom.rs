@@ -0,0 +1,130 @@
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+use crate::atom_base::*;
+use crate::getter_setter::Getter;
+
+type Read<T, E> = Box<dyn Fn(&mut Getter) -> Result<T, E> + Send + Sync>;
+
+/// A derived atom whose read can fail. The `Err` is cached and delivered like any other value,
+/// dependents can propagate it with `Getter::try_get` and `JotaiStore::retry` recomputes it.
+pub struct ResultAtom<T, E> {
+    id: Arc<AtomId>,
+    read: Read<T, E>,
+    equals: Equals<Result<T, E>>,
+}
+impl<T, E> PartialEq for ResultAtom<T, E> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+impl<T, E> Eq for ResultAtom<T, E> {}
+impl<T, E> Hash for ResultAtom<T, E> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+impl<T: PartialEq + 'static, E: PartialEq + 'static> ResultAtom<T, E> {
+    pub fn new<F>(f: F) -> Self
+    where
+        F: Fn(&mut Getter) -> Result<T, E> + 'static + Send + Sync,
+    {
+        Self::new_with_equals(f, default_equals())
+    }
+}
+impl<T: 'static, E: 'static> ResultAtom<T, E> {
+    /// For values or errors without `PartialEq`, or where comparing them is too expensive
+    pub fn new_with_equals<F>(f: F, equals: Equals<Result<T, E>>) -> Self
+    where
+        F: Fn(&mut Getter) -> Result<T, E> + 'static + Send + Sync,
+    {
+        Self {
+            id: Arc::new(AtomId::new()),
+            read: Box::new(f),
+            equals,
+        }
+    }
+
+    /// Replaces the equality used to decide whether a recomputed result notifies dependents
+    pub fn with_equals<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&Result<T, E>, &Result<T, E>) -> bool + 'static + Send + Sync,
+    {
+        self.equals = Arc::new(f);
+        self
+    }
+}
+impl<T, E> Atom for ResultAtom<T, E> {
+    fn get_id(&self) -> Arc<AtomId> {
+        self.id.clone()
+    }
+}
+impl<T, E> ReadAtom<Result<T, E>> for ResultAtom<T, E> {
+    fn get_read(&self) -> &Box<dyn Fn(&mut Getter) -> Result<T, E> + Send + Sync> {
+        &self.read
+    }
+    fn is_equal(&self, a: &Result<T, E>, b: &Result<T, E>) -> bool {
+        (self.equals)(a, b)
+    }
+    fn is_error(&self, value: &Result<T, E>) -> bool {
+        value.is_err()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use crate::*;
+
+    #[test]
+    fn test_result_atom() {
+        let store = JotaiStore::new();
+        let attempts = Arc::new(Mutex::new(0));
+        let available_atom = Arc::new(atom(false));
+        let source_atom = Arc::new(result_atom({
+            let attempts = attempts.clone();
+            move |_| {
+                let mut attempts = attempts.lock().unwrap();
+                *attempts += 1;
+                match *attempts {
+                    1 => Err("offline".to_string()),
+                    n => Ok(n),
+                }
+            }
+        }));
+        let other_reads = Arc::new(Mutex::new(0));
+        let other_atom = Arc::new(select_atom({
+            let (available_atom, other_reads) = (available_atom.clone(), other_reads.clone());
+            move |getter| {
+                *other_reads.lock().unwrap() += 1;
+                *getter.get(available_atom.clone())
+            }
+        }));
+        let label_atom = Arc::new(result_atom({
+            let (source_atom, other_atom) = (source_atom.clone(), other_atom.clone());
+            move |getter| {
+                let other = getter.get(other_atom.clone());
+                let count = getter.try_get(source_atom.clone())?;
+                Ok::<_, String>(format!("{count} {other}"))
+            }
+        }));
+        let seen = Arc::new(Mutex::new(vec![]));
+        let _dispose = store.clone().sub_value(label_atom.clone(), {
+            let seen = seen.clone();
+            move |value, _| seen.lock().unwrap().push((*value).clone())
+        });
+        assert_eq!(*store.clone().get(&*label_atom), Err("offline".to_string()));
+
+        // Only the failed atoms are recomputed
+        store.retry();
+        assert_eq!(*store.clone().get(&*label_atom), Ok("2 false".to_string()));
+        assert_eq!(*attempts.lock().unwrap(), 2);
+        assert_eq!(*other_reads.lock().unwrap(), 1);
+        assert_eq!(*seen.lock().unwrap(), vec![Ok("2 false".to_string())]);
+
+        // Nothing failed, so nothing to retry
+        store.retry();
+        assert_eq!(*attempts.lock().unwrap(), 2);
+    }
+}
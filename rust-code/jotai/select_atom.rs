@@ -1,12 +1,13 @@
 use std::hash::{Hash, Hasher};
 use std::sync::Arc;
 
-use crate::atom_base::{Atom, AtomId, ReadAtom};
+use crate::atom_base::{default_equals, Atom, AtomId, Equals, ReadAtom};
 use crate::getter_setter::Getter;
 
 pub struct SelectAtom<T> {
     id: Arc<AtomId>,
     read: Box<dyn Fn(&mut Getter) -> T + Send + Sync>,
+    equals: Equals<T>,
 }
 impl<T> PartialEq for SelectAtom<T> {
     fn eq(&self, other: &Self) -> bool {
@@ -19,16 +20,35 @@ impl<T> Hash for SelectAtom<T> {
         self.id.hash(state);
     }
 }
-impl<T: 'static> SelectAtom<T> {
+impl<T: PartialEq + 'static> SelectAtom<T> {
     pub fn new<F>(f: F) -> Self
+    where
+        F: Fn(&mut Getter) -> T + 'static + Send + Sync,
+    {
+        Self::new_with_equals(f, default_equals())
+    }
+}
+impl<T: 'static> SelectAtom<T> {
+    /// For values without `PartialEq`, or where comparing them is too expensive
+    pub fn new_with_equals<F>(f: F, equals: Equals<T>) -> Self
     where
         F: Fn(&mut Getter) -> T + 'static + Send + Sync,
     {
         Self {
             id: Arc::new(AtomId::new()),
             read: Box::new(f),
+            equals,
         }
     }
+
+    /// Replaces the equality used to decide whether a recomputed value notifies dependents
+    pub fn with_equals<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&T, &T) -> bool + 'static + Send + Sync,
+    {
+        self.equals = Arc::new(f);
+        self
+    }
 }
 impl<T> Atom for SelectAtom<T> {
     fn get_id(&self) -> Arc<AtomId> {
@@ -39,4 +59,7 @@ impl<T> ReadAtom<T> for SelectAtom<T> {
     fn get_read(&self) -> &Box<dyn Fn(&mut Getter) -> T + Send + Sync> {
         &self.read
     }
+    fn is_equal(&self, a: &T, b: &T) -> bool {
+        (self.equals)(a, b)
+    }
 }
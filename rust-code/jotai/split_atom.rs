@@ -0,0 +1,355 @@
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex, Weak};
+
+use weak_table::WeakValueHashMap;
+
+use crate::atom_base::*;
+use crate::getter_setter::{Getter, Setter};
+use crate::primitive_atom::PrimitiveAtom;
+use crate::select_atom::SelectAtom;
+use crate::writable_atom::WritableAtom;
+
+type Key<T, K> = Arc<dyn Fn(usize, &T) -> K + Send + Sync>;
+type Elements<T> = Vec<Arc<WritableAtom<T, T>>>;
+type Read<T> = Box<dyn Fn(&mut Getter) -> Elements<T> + Send + Sync>;
+// Unchanged elements keep their `Arc` between list versions, so each can be compared by pointer
+type ElementValues<T, K> = Arc<SelectAtom<HashMap<K, Arc<T>>>>;
+// Like atom families, element atoms live as long as someone holds them
+type ElementCache<T, K> = Arc<Mutex<WeakValueHashMap<K, Weak<WritableAtom<T, T>>>>>;
+
+pub enum SplitAction<T> {
+    /// Inserts `value` at `index`, or at the end if `index` is past it
+    Insert { index: usize, value: T },
+    /// Removes the element behind one of the split's atoms
+    Remove(Arc<WritableAtom<T, T>>),
+}
+
+/// Reads as one writable atom per element of a list atom. Element atoms are matched by key, so an
+/// element keeps its atom when the list is reordered, and only re-run and notify when their element
+/// changes.
+pub struct SplitAtom<T, K> {
+    id: Arc<AtomId>,
+    list: Arc<PrimitiveAtom<Vec<T>>>,
+    positions: Arc<SelectAtom<HashMap<K, usize>>>,
+    read: Read<T>,
+    elements: ElementCache<T, K>,
+}
+impl<T, K> PartialEq for SplitAtom<T, K> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+impl<T, K> Eq for SplitAtom<T, K> {}
+impl<T, K> Hash for SplitAtom<T, K> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+impl<T: Clone + PartialEq + Send + Sync + 'static> SplitAtom<T, usize> {
+    /// Elements are matched by index, so after a reorder each atom reads its new element
+    pub fn new(list: Arc<PrimitiveAtom<Vec<T>>>) -> Self {
+        Self::new_with_index_key(list, Arc::new(|i, _| i))
+    }
+}
+impl<T, K> SplitAtom<T, K>
+where
+    T: Clone + PartialEq + Send + Sync + 'static,
+    K: Hash + Eq + Clone + Send + Sync + 'static,
+{
+    /// Elements are matched by `key`, which has to be unique within the list
+    pub fn new_with_key<F>(list: Arc<PrimitiveAtom<Vec<T>>>, key: F) -> Self
+    where
+        F: Fn(&T) -> K + 'static + Send + Sync,
+    {
+        Self::new_with_index_key(list, Arc::new(move |_, value| key(value)))
+    }
+
+    fn new_with_index_key(list: Arc<PrimitiveAtom<Vec<T>>>, key: Key<T, K>) -> Self {
+        let positions = Arc::new(SelectAtom::new({
+            let (list, key) = (list.clone(), key.clone());
+            move |getter| {
+                let list = getter.get(list.clone());
+                let positions = list.iter().enumerate();
+                positions.map(|(i, value)| (key(i, value), i)).collect()
+            }
+        }));
+        let element_values: ElementValues<T, K> = Arc::new(SelectAtom::new_with_equals(
+            {
+                let (list, key) = (list.clone(), key.clone());
+                let previous = Mutex::new(HashMap::<K, Arc<T>>::new());
+                move |getter| {
+                    let list = getter.get(list.clone());
+                    let mut previous = previous.lock().unwrap();
+                    let values: HashMap<K, Arc<T>> = list
+                        .iter()
+                        .enumerate()
+                        .map(|(i, value)| {
+                            let key = key(i, value);
+                            let value = match previous.get(&key) {
+                                Some(shared) if **shared == *value => shared.clone(),
+                                _ => Arc::new(value.clone()),
+                            };
+                            (key, value)
+                        })
+                        .collect();
+                    *previous = values.clone();
+                    values
+                }
+            },
+            Arc::new(|a: &HashMap<K, Arc<T>>, b: &HashMap<K, Arc<T>>| {
+                a.len() == b.len()
+                    && a.iter()
+                        .all(|(key, a)| b.get(key).is_some_and(|b| Arc::ptr_eq(a, b)))
+            }),
+        ));
+        let elements: ElementCache<T, K> = Arc::new(Mutex::new(WeakValueHashMap::new()));
+        let read = {
+            let (list, positions, elements) = (list.clone(), positions.clone(), elements.clone());
+            move |getter: &mut Getter| {
+                let values = getter.get(list.clone());
+                let mut elements = elements.lock().unwrap();
+                values
+                    .iter()
+                    .enumerate()
+                    .map(|(i, value)| {
+                        let key = key(i, value);
+                        if let Some(atom) = elements.get(&key) {
+                            return atom;
+                        }
+                        let atom = Arc::new(element_atom(
+                            list.clone(),
+                            positions.clone(),
+                            element_values.clone(),
+                            key.clone(),
+                            value.clone(),
+                        ));
+                        elements.insert(key, atom.clone());
+                        atom
+                    })
+                    .collect()
+            }
+        };
+        Self {
+            id: Arc::new(AtomId::new()),
+            list,
+            positions,
+            read: Box::new(read),
+            elements,
+        }
+    }
+}
+impl<T, K> Atom for SplitAtom<T, K> {
+    fn get_id(&self) -> Arc<AtomId> {
+        self.id.clone()
+    }
+}
+impl<T, K> ReadAtom<Elements<T>> for SplitAtom<T, K>
+where
+    T: Send + Sync,
+    K: Send + Sync,
+{
+    fn get_read(&self) -> &Box<dyn Fn(&mut Getter) -> Elements<T> + Send + Sync> {
+        &self.read
+    }
+    // Only changes when elements are inserted, removed or moved
+    fn is_equal(&self, a: &Elements<T>, b: &Elements<T>) -> bool {
+        a.len() == b.len() && a.iter().zip(b).all(|(a, b)| Arc::ptr_eq(a, b))
+    }
+}
+impl<T, K> WriteAtom<SplitAction<T>> for SplitAtom<T, K>
+where
+    T: Clone + Send + Sync + 'static,
+    K: Hash + Eq + Clone + Send + Sync + 'static,
+{
+    fn write(&self, setter: &mut Setter, action: Arc<SplitAction<T>>) {
+        let mut list = (*setter.get(self.list.clone())).clone();
+        match &*action {
+            SplitAction::Insert { index, value } => {
+                list.insert((*index).min(list.len()), value.clone());
+            }
+            SplitAction::Remove(atom) => {
+                let key = {
+                    let elements = self.elements.lock().unwrap();
+                    let mut elements = elements.iter();
+                    elements
+                        .find_map(|(key, element)| Arc::ptr_eq(&element, atom).then(|| key.clone()))
+                };
+                let positions = setter.get(self.positions.clone());
+                let Some(&index) = key.and_then(|key| positions.get(&key)) else {
+                    return;
+                };
+                list.remove(index);
+            }
+        }
+        setter.set_primitive(&self.list, Arc::new(list));
+    }
+}
+
+fn element_atom<T, K>(
+    list: Arc<PrimitiveAtom<Vec<T>>>,
+    positions: Arc<SelectAtom<HashMap<K, usize>>>,
+    element_values: ElementValues<T, K>,
+    key: K,
+    value: T,
+) -> WritableAtom<T, T>
+where
+    T: Clone + PartialEq + Send + Sync + 'static,
+    K: Hash + Eq + Send + Sync + 'static,
+{
+    let key = Arc::new(key);
+    // Re-runs for every list change, but only passes it on when this element's `Arc` changed
+    let slot = Arc::new(SelectAtom::new_with_equals(
+        {
+            let key = key.clone();
+            move |getter| getter.get(element_values.clone()).get(&*key).cloned()
+        },
+        Arc::new(|a: &Option<Arc<T>>, b: &Option<Arc<T>>| match (a, b) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        }),
+    ));
+    // Once removed from the list, the atom keeps reading the element's last value
+    let last = Mutex::new(value);
+    WritableAtom::new(
+        move |getter| {
+            let mut last = last.lock().unwrap();
+            if let Some(value) = &*getter.get(slot.clone()) {
+                *last = (**value).clone();
+            }
+            last.clone()
+        },
+        move |setter: &mut Setter, value: Arc<T>| {
+            let Some(&index) = setter.get(positions.clone()).get(&*key) else {
+                return;
+            };
+            let mut values = (*setter.get(list.clone())).clone();
+            values[index] = (*value).clone();
+            setter.set_primitive(&list, Arc::new(values));
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use crate::*;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Row {
+        id: u32,
+        text: String,
+    }
+    fn row(id: u32, text: &str) -> Row {
+        Row {
+            id,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_split_atom() {
+        let store = JotaiStore::new();
+        let rows_atom = Arc::new(atom(vec![row(1, "a"), row(2, "b")]));
+        let split = Arc::new(split_atom_with_key(rows_atom.clone(), |row| row.id));
+        let list_notified = Arc::new(Mutex::new(0));
+        let _dispose = store.clone().sub(split.clone(), {
+            let list_notified = list_notified.clone();
+            move |_| *list_notified.lock().unwrap() += 1
+        });
+        let atoms = store.clone().get(&*split);
+        let (first, second) = (atoms[0].clone(), atoms[1].clone());
+        let first_texts = Arc::new(Mutex::new(vec![]));
+        let _dispose_first = store.clone().sub_value(first.clone(), {
+            let first_texts = first_texts.clone();
+            move |row, _| first_texts.lock().unwrap().push(row.text.clone())
+        });
+
+        // Editing a row only notifies its own subscribers
+        store.clone().set(&*second, Arc::new(row(2, "b2")));
+        assert_eq!(
+            *store.clone().get(&*rows_atom),
+            vec![row(1, "a"), row(2, "b2")]
+        );
+        assert!(first_texts.lock().unwrap().is_empty());
+        assert_eq!(*list_notified.lock().unwrap(), 0);
+
+        // Atoms follow their rows when the list is reordered
+        store
+            .clone()
+            .set_primitive(&rows_atom, Arc::new(vec![row(2, "b2"), row(1, "a")]));
+        let atoms = store.clone().get(&*split);
+        assert!(Arc::ptr_eq(&atoms[0], &second) && Arc::ptr_eq(&atoms[1], &first));
+        assert!(first_texts.lock().unwrap().is_empty());
+        store.clone().set(&*first, Arc::new(row(1, "a2")));
+        assert_eq!(*first_texts.lock().unwrap(), vec!["a2".to_string()]);
+
+        store.clone().set(
+            &*split,
+            Arc::new(SplitAction::Insert {
+                index: 0,
+                value: row(3, "c"),
+            }),
+        );
+        store
+            .clone()
+            .set(&*split, Arc::new(SplitAction::Remove(second.clone())));
+        assert_eq!(
+            *store.clone().get(&*rows_atom),
+            vec![row(3, "c"), row(1, "a2")]
+        );
+        assert_eq!(*list_notified.lock().unwrap(), 3);
+    }
+
+    #[test]
+    fn test_split_atom_recomputes_edited_row() {
+        let store = JotaiStore::new();
+        let rows: Vec<_> = (0..100).map(|id| row(id, "a")).collect();
+        let rows_atom = Arc::new(atom(rows));
+        let split = Arc::new(split_atom_with_key(rows_atom.clone(), |row| row.id));
+        let atoms = store.clone().get(&*split);
+        let _disposes: Vec<_> = atoms
+            .iter()
+            .map(|atom| store.clone().sub(atom.clone(), |_| {}))
+            .collect();
+        let recomputed = Arc::new(Mutex::new(vec![]));
+        let _remove = store.add_interceptor({
+            let recomputed = recomputed.clone();
+            move |event| {
+                if event.kind == StoreEventKind::Recompute {
+                    recomputed.lock().unwrap().push(event.atom_id);
+                }
+            }
+        });
+
+        // Editing a single row only re-runs that row's atom
+        store.clone().set(&*atoms[42], Arc::new(row(42, "b")));
+        let recomputed = recomputed.lock().unwrap();
+        let rows_recomputed: Vec<_> = atoms
+            .iter()
+            .filter(|atom| recomputed.contains(&atom.get_id()))
+            .collect();
+        assert_eq!(rows_recomputed.len(), 1);
+        assert!(Arc::ptr_eq(rows_recomputed[0], &atoms[42]));
+        assert_eq!(store.clone().get(&*atoms[42]).text, "b");
+    }
+
+    #[test]
+    fn test_split_atom_by_index() {
+        let store = JotaiStore::new();
+        let values_atom = Arc::new(atom(vec![1, 2, 3]));
+        let split = split_atom(values_atom.clone());
+        let atoms = store.clone().get(&split);
+        store.clone().set(&*atoms[1], Arc::new(20));
+        assert_eq!(*store.clone().get(&*values_atom), vec![1, 20, 3]);
+
+        // Reordering keeps the atoms, they read whatever is at their index
+        store
+            .clone()
+            .set_primitive(&values_atom, Arc::new(vec![3, 20, 1]));
+        assert!(Arc::ptr_eq(&store.clone().get(&split)[0], &atoms[0]));
+        assert_eq!(*store.clone().get(&*atoms[0]), 3);
+    }
+}
@@ -0,0 +1,87 @@
+use rusqlite::{Connection, OptionalExtension};
+use rusqlite_migrations::{Migrations, M};
+use std::{rc::Rc, sync::OnceLock};
+
+const MIGRATIONS_SLICE: &[M<'_>] = &[M::up(
+    "
+    CREATE TABLE IF NOT EXISTS atom_storage(
+      key TEXT PRIMARY KEY,
+      value TEXT NOT NULL,
+      updated_at INTEGER DEFAULT (unixepoch('subsec') * 1000)
+    )
+    ",
+)
+.down("DROP TABLE IF EXISTS atom_storage")];
+const MIGRATIONS: Migrations<'_> = Migrations::from_slice(MIGRATIONS_SLICE);
+
+#[cfg(not(test))]
+pub(crate) fn get_conn() -> Option<Rc<Connection>> {
+    let path = DB_PATH.get()?;
+    let conn = DB_CONN.with(|v| {
+        v.get_or_init(|| {
+            let mut conn = Connection::open(path).unwrap();
+            MIGRATIONS.to_latest(&mut conn).unwrap();
+            Rc::new(conn)
+        })
+        .clone()
+    });
+    Some(conn)
+}
+#[cfg(test)]
+pub(crate) fn get_conn() -> Option<Rc<Connection>> {
+    let conn = DB_CONN.with(|v| {
+        v.get_or_init(|| {
+            // Test just opens in memory db
+            let mut conn = Connection::open_in_memory().unwrap();
+            MIGRATIONS.to_latest(&mut conn).unwrap();
+            Rc::new(conn)
+        })
+        .clone()
+    });
+    Some(conn)
+}
+
+pub fn set_db_path(path: &str) {
+    DB_PATH.get_or_init(|| path.to_string());
+}
+
+static DB_PATH: OnceLock<String> = OnceLock::new();
+thread_local! {
+    static DB_CONN: OnceLock<Rc<Connection>> = const { OnceLock::new() };
+}
+
+pub fn load_value(key: &str) -> Result<Option<String>, rusqlite::Error> {
+    let Some(conn) = get_conn() else {
+        return Ok(None);
+    };
+    conn.query_row(
+        "SELECT value FROM atom_storage WHERE key = ?;",
+        (key,),
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+pub fn save_value(key: &str, value: &str) -> Result<usize, rusqlite::Error> {
+    let Some(conn) = get_conn() else { return Ok(0) };
+    conn.execute(
+        "INSERT INTO atom_storage (key, value) VALUES (?, ?)
+        ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = unixepoch('subsec') * 1000;",
+        (key, value),
+    )
+}
+
+pub fn remove_value(key: &str) -> Result<usize, rusqlite::Error> {
+    let Some(conn) = get_conn() else { return Ok(0) };
+    conn.execute("DELETE FROM atom_storage WHERE key = ?;", (key,))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migrations_test() {
+        assert!(MIGRATIONS.validate().is_ok());
+    }
+}
@@ -0,0 +1,111 @@
+mod db;
+
+pub use crate::db::{remove_value, set_db_path};
+use jotai::PrimitiveAtom;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// A primitive atom whose value is persisted under `key`.
+/// It hydrates from the database on first read (falling back to `default_value` when missing or
+/// unreadable) and writes through on every change. Resetting it stores `default_value`.
+pub fn atom_with_storage<T>(key: &str, default_value: T) -> PrimitiveAtom<T>
+where
+    T: Serialize + DeserializeOwned + Clone + PartialEq + Send + Sync + 'static,
+{
+    let read_key = key.to_string();
+    let write_key = key.to_string();
+    let initial = default_value.clone();
+    PrimitiveAtom::new_fn(Box::new(move |_| {
+        db::load_value(&read_key)
+            .ok()
+            .flatten()
+            .and_then(|value| serde_json::from_str(&value).ok())
+            .unwrap_or_else(|| default_value.clone())
+    }))
+    .with_initial(initial)
+    .with_on_set(move |value| {
+        if let Ok(value) = serde_json::to_string(value) {
+            _ = db::save_value(&write_key, &value);
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jotai::JotaiStore;
+    use serde::Deserialize;
+    use std::sync::Arc;
+
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    struct Settings {
+        name: String,
+        count: u32,
+    }
+
+    #[test]
+    fn test_atom_with_storage() {
+        let default_settings = Settings {
+            name: "default".into(),
+            count: 0,
+        };
+        let store = JotaiStore::new();
+        let settings_atom = atom_with_storage("settings", default_settings.clone());
+        assert_eq!(*store.clone().get(&settings_atom), default_settings);
+        let updated = Settings {
+            name: "updated".into(),
+            count: 2,
+        };
+        store.set_primitive(&settings_atom, Arc::new(updated.clone()));
+
+        // Simulates a restart, a fresh store and atom hydrate from the db
+        let store = JotaiStore::new();
+        let settings_atom = atom_with_storage("settings", default_settings.clone());
+        assert_eq!(*store.clone().get(&settings_atom), updated);
+
+        _ = remove_value("settings");
+        let store = JotaiStore::new();
+        let settings_atom = atom_with_storage("settings", default_settings.clone());
+        assert_eq!(*store.clone().get(&settings_atom), default_settings);
+    }
+
+    #[test]
+    fn test_atom_with_storage_reset() {
+        _ = db::save_value("reset", "3");
+        let store = JotaiStore::new();
+        let reset_atom = atom_with_storage("reset", 0u32);
+        assert_eq!(*store.clone().get(&reset_atom), 3);
+
+        store.clone().reset(&reset_atom);
+        assert_eq!(*store.clone().get(&reset_atom), 0);
+        let fresh_atom = atom_with_storage("reset", 0u32);
+        assert_eq!(*JotaiStore::new().get(&fresh_atom), 0);
+    }
+
+    #[test]
+    fn test_atom_with_storage_undo() {
+        _ = db::save_value("undo", "1");
+        let store = JotaiStore::new();
+        store.enable_history(10);
+        let undo_atom = atom_with_storage("undo", 0u32);
+        store.set_primitive(&undo_atom, Arc::new(2));
+
+        // The first write goes back to the loaded value, in the store and in the db
+        assert!(store.undo());
+        assert_eq!(*store.clone().get(&undo_atom), 1);
+        let fresh_atom = atom_with_storage("undo", 0u32);
+        assert_eq!(*JotaiStore::new().get(&fresh_atom), 1);
+
+        assert!(store.redo());
+        let fresh_atom = atom_with_storage("undo", 0u32);
+        assert_eq!(*JotaiStore::new().get(&fresh_atom), 2);
+    }
+
+    #[test]
+    fn test_atom_with_storage_unreadable() {
+        _ = db::save_value("count", "not json");
+        let store = JotaiStore::new();
+        let count_atom = atom_with_storage("count", 5u32);
+        assert_eq!(*store.clone().get(&count_atom), 5);
+    }
+}
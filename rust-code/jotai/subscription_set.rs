@@ -1,7 +1,5 @@
-use std::cell::RefCell;
-use std::rc::Rc;
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::{Arc, Weak};
+use std::sync::{Arc, Mutex, Weak};
 
 use weak_table::WeakKeyHashMap;
 
@@ -10,29 +8,56 @@ fn new_closure_id() -> usize {
     NEXT_CLOSURE_ID.fetch_add(1, Ordering::Relaxed)
 }
 pub(crate) struct SubscriptionSet<T> {
-    callbacks: Rc<RefCell<WeakKeyHashMap<Weak<usize>, Box<dyn Fn(&T)>>>>,
+    callbacks: Arc<Mutex<WeakKeyHashMap<Weak<usize>, Arc<dyn Fn(&T) + Send + Sync>>>>,
 }
 impl<T: 'static> SubscriptionSet<T> {
     pub(crate) fn new() -> Self {
         Self {
-            callbacks: Rc::new(RefCell::new(WeakKeyHashMap::new())),
+            callbacks: Arc::new(Mutex::new(WeakKeyHashMap::new())),
         }
     }
-    pub(crate) fn sub<F: Fn(&T) + 'static>(&self, f: F) -> Box<dyn FnOnce()> {
+    pub(crate) fn sub<F: Fn(&T) + 'static + Send + Sync>(&self, f: F) -> Box<dyn FnOnce() + Send> {
         let closure_id = Arc::new(new_closure_id());
         let callbacks = self.callbacks.clone();
         self.callbacks
-            .borrow_mut()
-            .insert(closure_id.clone(), Box::new(f));
+            .lock()
+            .unwrap()
+            .insert(closure_id.clone(), Arc::new(f));
         return Box::new(move || {
-            callbacks.borrow_mut().remove(&closure_id);
+            callbacks.lock().unwrap().remove(&closure_id);
         });
     }
+    /// Like `sub`, but `f` stays subscribed until the returned function is called, even if it's
+    /// dropped
+    pub(crate) fn sub_until_removed<F: Fn(&T) + 'static + Send + Sync>(
+        &self,
+        f: F,
+    ) -> Box<dyn FnOnce() + Send> {
+        let closure_id = Arc::new(new_closure_id());
+        let callbacks = self.callbacks.clone();
+        // The callback holds on to its own key, so only removing it drops the entry
+        let key = closure_id.clone();
+        self.callbacks.lock().unwrap().insert(
+            closure_id.clone(),
+            Arc::new(move |v: &T| {
+                let _key = &key;
+                f(v)
+            }),
+        );
+        Box::new(move || {
+            callbacks.lock().unwrap().remove(&closure_id);
+        })
+    }
     pub(crate) fn notify(&self, v: &T) {
-        self.callbacks.borrow().values().for_each(|f| f(v));
+        // Callbacks may (un)subscribe, so don't hold the lock while calling them
+        let callbacks: Vec<_> = self.callbacks.lock().unwrap().values().cloned().collect();
+        callbacks.iter().for_each(|f| f(v));
     }
     pub(crate) fn is_empty(&self) -> bool {
-        self.callbacks.borrow().keys().count() == 0
+        self.len() == 0
+    }
+    pub(crate) fn len(&self) -> usize {
+        self.callbacks.lock().unwrap().keys().count()
     }
 }
 
@@ -43,28 +68,28 @@ mod tests {
     #[test]
     fn test_subscription_set() {
         let set = SubscriptionSet::new();
-        let counter_ref = Rc::new(RefCell::new(0));
-        let counter2_ref = Rc::new(RefCell::new(10));
+        let counter_ref = Arc::new(Mutex::new(0));
+        let counter2_ref = Arc::new(Mutex::new(10));
         let counter_ref1 = counter_ref.clone();
         let dispose1 = set.sub(move |v| {
-            *(counter_ref1.borrow_mut()) += v;
+            *(counter_ref1.lock().unwrap()) += v;
         });
         let counter2_ref1 = counter2_ref.clone();
         let dispose2 = set.sub(move |v| {
-            *counter2_ref1.borrow_mut() += v * 2;
+            *counter2_ref1.lock().unwrap() += v * 2;
         });
         set.notify(&3);
-        assert_eq!(*counter_ref.borrow(), 3);
-        assert_eq!(*counter2_ref.borrow(), 16);
+        assert_eq!(*counter_ref.lock().unwrap(), 3);
+        assert_eq!(*counter2_ref.lock().unwrap(), 16);
         dispose2();
         set.notify(&4);
-        assert_eq!(*counter_ref.borrow(), 7);
-        assert_eq!(*counter2_ref.borrow(), 16);
+        assert_eq!(*counter_ref.lock().unwrap(), 7);
+        assert_eq!(*counter2_ref.lock().unwrap(), 16);
         // FnOnce means you can't call twice
         // dispose2();
         dispose1();
         set.notify(&4);
-        assert_eq!(*counter_ref.borrow(), 7);
-        assert_eq!(*counter2_ref.borrow(), 16);
+        assert_eq!(*counter_ref.lock().unwrap(), 7);
+        assert_eq!(*counter2_ref.lock().unwrap(), 16);
     }
 }
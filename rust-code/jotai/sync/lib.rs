@@ -0,0 +1,451 @@
+mod transport;
+
+pub use crate::transport::{ChannelTransport, SyncTransport};
+use jotai::{JotaiStore, PrimitiveAtom, ReadAtom};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The value of a synced atom, last written at `timestamp` by `replica`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SyncEntry {
+    pub key: String,
+    pub value: Value,
+    pub timestamp: u64,
+    pub replica: String,
+}
+impl SyncEntry {
+    /// Last writer wins, ties go to the greater replica name so both sides pick the same value
+    fn wins_over(&self, other: &SyncEntry) -> bool {
+        (self.timestamp, &self.replica) > (other.timestamp, &other.replica)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum SyncMessage {
+    /// Sent on start and reconnect with the full state, the other side answers with its own
+    Hello {
+        entries: Vec<SyncEntry>,
+    },
+    State {
+        entries: Vec<SyncEntry>,
+    },
+    Diff {
+        entries: Vec<SyncEntry>,
+    },
+}
+
+type Dispose = Box<dyn Fn() + Send + Sync>;
+type OnChange = Box<dyn Fn() + Send + Sync>;
+type SetValue = Box<dyn FnOnce(&Arc<JotaiStore>)>;
+type Read = Box<dyn Fn(&Arc<JotaiStore>) -> Option<Value> + Send + Sync>;
+type Written = Box<dyn Fn(&Arc<JotaiStore>) -> bool + Send + Sync>;
+type Decode = Box<dyn Fn(&Value) -> Option<SetValue> + Send + Sync>;
+type Sub = Box<dyn Fn(&Arc<JotaiStore>, OnChange) -> Dispose + Send + Sync>;
+
+// Type erased, so atoms of any value type fit in one map
+struct SyncedAtom {
+    read: Read,
+    written: Written,
+    decode: Decode,
+    sub: Sub,
+}
+
+/// Mirrors primitive atoms with another store over a `SyncTransport`. Atoms are matched by key,
+/// values travel as JSON. Values still at the atom's initial value have timestamp 0 so any write
+/// wins over them, values written before `start` are stamped with the time it was called.
+pub struct StoreSync {
+    store: Arc<JotaiStore>,
+    replica: String,
+    transport: Box<dyn SyncTransport>,
+    clock: Box<dyn Fn() -> u64 + Send + Sync>,
+    atoms: HashMap<String, SyncedAtom>,
+    versions: Mutex<HashMap<String, SyncEntry>>,
+    disposes: Mutex<Vec<Dispose>>,
+}
+impl StoreSync {
+    /// `replica` names this side, it has to differ from the other side's
+    pub fn new(
+        store: Arc<JotaiStore>,
+        replica: &str,
+        transport: impl SyncTransport + 'static,
+    ) -> Self {
+        Self {
+            store,
+            replica: replica.to_string(),
+            transport: Box::new(transport),
+            clock: Box::new(|| {
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |d| d.as_millis() as u64)
+            }),
+            atoms: HashMap::new(),
+            versions: Mutex::new(HashMap::new()),
+            disposes: Mutex::new(vec![]),
+        }
+    }
+
+    /// Replaces the millisecond clock used to timestamp writes, e.g. with `Date.now` on wasm
+    pub fn with_clock<F>(mut self, clock: F) -> Self
+    where
+        F: Fn() -> u64 + 'static + Send + Sync,
+    {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// Syncs `atom` under `key`, values the other side can't deserialize are ignored. Atoms
+    /// without an initial value always count as written.
+    pub fn with_atom<T>(mut self, key: &str, atom: Arc<PrimitiveAtom<T>>) -> Self
+    where
+        T: Serialize + DeserializeOwned + Send + Sync + 'static,
+    {
+        let read = {
+            let atom = atom.clone();
+            move |store: &Arc<JotaiStore>| serde_json::to_value(&*store.clone().get(&*atom)).ok()
+        };
+        let written = {
+            let atom = atom.clone();
+            move |store: &Arc<JotaiStore>| {
+                let value = store.clone().get(&*atom);
+                atom.initial()
+                    .is_none_or(|initial| !atom.is_equal(&initial, &value))
+            }
+        };
+        let decode = {
+            let atom = atom.clone();
+            move |value: &Value| -> Option<SetValue> {
+                let value: T = serde_json::from_value(value.clone()).ok()?;
+                let atom = atom.clone();
+                Some(Box::new(move |store| {
+                    store.set_primitive(&atom, Arc::new(value))
+                }))
+            }
+        };
+        let sub = move |store: &Arc<JotaiStore>, on_change: OnChange| {
+            let dispose: Dispose = Box::new(store.clone().sub(atom.clone(), move |_| on_change()));
+            dispose
+        };
+        self.atoms.insert(
+            key.to_string(),
+            SyncedAtom {
+                read: Box::new(read),
+                written: Box::new(written),
+                decode: Box::new(decode),
+                sub: Box::new(sub),
+            },
+        );
+        self
+    }
+
+    /// Sends the current state and keeps both stores in sync until dropped
+    pub fn start(self) -> Arc<Self> {
+        let started_at = (self.clock)();
+        let sync = Arc::new(self);
+        {
+            let mut versions = sync.versions.lock().unwrap();
+            for (key, atom) in &sync.atoms {
+                let Some(value) = (atom.read)(&sync.store) else {
+                    continue;
+                };
+                let timestamp = if (atom.written)(&sync.store) {
+                    started_at
+                } else {
+                    0
+                };
+                versions.insert(key.clone(), sync.entry(key, value, timestamp));
+            }
+        }
+        let mut disposes = vec![];
+        for (key, atom) in &sync.atoms {
+            let weak = Arc::downgrade(&sync);
+            let key = key.clone();
+            let on_change = Box::new(move || {
+                if let Some(sync) = weak.upgrade() {
+                    sync.local_change(&key);
+                }
+            });
+            disposes.push((atom.sub)(&sync.store, on_change));
+        }
+        *sync.disposes.lock().unwrap() = disposes;
+
+        let weak: Weak<Self> = Arc::downgrade(&sync);
+        sync.transport.on_message(Box::new(move |message| {
+            if let Some(sync) = weak.upgrade() {
+                sync.receive(&message);
+            }
+        }));
+        let weak: Weak<Self> = Arc::downgrade(&sync);
+        sync.transport.on_connect(Box::new(move || {
+            if let Some(sync) = weak.upgrade() {
+                sync.resync();
+            }
+        }));
+        sync.resync();
+        sync
+    }
+
+    /// Exchanges the full state with the other side, so changes lost in transit still arrive.
+    /// Transports call it through `on_connect`.
+    pub fn resync(&self) {
+        self.send(SyncMessage::Hello {
+            entries: self.entries(),
+        });
+    }
+
+    fn entry(&self, key: &str, value: Value, timestamp: u64) -> SyncEntry {
+        SyncEntry {
+            key: key.to_string(),
+            value,
+            timestamp,
+            replica: self.replica.clone(),
+        }
+    }
+
+    fn entries(&self) -> Vec<SyncEntry> {
+        self.versions.lock().unwrap().values().cloned().collect()
+    }
+
+    fn send(&self, message: SyncMessage) {
+        if let Ok(message) = serde_json::to_string(&message) {
+            self.transport.send(message);
+        }
+    }
+
+    fn local_change(&self, key: &str) {
+        let Some(value) = self
+            .atoms
+            .get(key)
+            .and_then(|atom| (atom.read)(&self.store))
+        else {
+            return;
+        };
+        let entry = {
+            let mut versions = self.versions.lock().unwrap();
+            let previous = versions.get(key);
+            // Also skips the notification for a value that was just received
+            if previous.is_some_and(|previous| previous.value == value) {
+                return;
+            }
+            // Never goes back in time, even if the clock does
+            let timestamp = (self.clock)().max(previous.map_or(0, |p| p.timestamp + 1));
+            let entry = self.entry(key, value, timestamp);
+            versions.insert(key.to_string(), entry.clone());
+            entry
+        };
+        self.send(SyncMessage::Diff {
+            entries: vec![entry],
+        });
+    }
+
+    fn receive(&self, message: &str) {
+        let Ok(message) = serde_json::from_str::<SyncMessage>(message) else {
+            return;
+        };
+        match message {
+            SyncMessage::Hello { entries } => {
+                self.apply(entries);
+                self.send(SyncMessage::State {
+                    entries: self.entries(),
+                });
+            }
+            SyncMessage::State { entries } | SyncMessage::Diff { entries } => self.apply(entries),
+        }
+    }
+
+    fn apply(&self, entries: Vec<SyncEntry>) {
+        for entry in entries {
+            let Some(atom) = self.atoms.get(&entry.key) else {
+                continue;
+            };
+            let Some(set) = (atom.decode)(&entry.value) else {
+                continue;
+            };
+            {
+                let mut versions = self.versions.lock().unwrap();
+                let wins = versions
+                    .get(&entry.key)
+                    .is_none_or(|local| entry.wins_over(local));
+                if !wins {
+                    continue;
+                }
+                versions.insert(entry.key.clone(), entry);
+            }
+            // Outside the lock, the store notifies `local_change`
+            set(&self.store);
+        }
+    }
+}
+impl Drop for StoreSync {
+    fn drop(&mut self) {
+        for dispose in self.disposes.lock().unwrap().drain(..) {
+            dispose();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jotai::atom;
+    use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    struct Settings {
+        name: String,
+        count: u32,
+    }
+
+    #[test]
+    fn test_sync_stores() {
+        let settings = Settings {
+            name: "default".into(),
+            count: 0,
+        };
+        let (phone_transport, watch_transport) = ChannelTransport::pair();
+        let phone = JotaiStore::new();
+        let phone_settings = Arc::new(atom(settings.clone()));
+        let phone_local = Arc::new(atom(0));
+        let watch = JotaiStore::new();
+        let watch_settings = Arc::new(atom(settings.clone()));
+        let watch_local = Arc::new(atom(0));
+
+        // Written before syncing, sent with the initial state
+        let updated = Settings {
+            name: "phone".into(),
+            count: 1,
+        };
+        phone.set_primitive(&phone_settings, Arc::new(updated.clone()));
+        let _phone_sync = StoreSync::new(phone.clone(), "phone", phone_transport)
+            .with_atom("settings", phone_settings.clone())
+            .start();
+        phone.set_primitive(&phone_local, Arc::new(1));
+        let _watch_sync = StoreSync::new(watch.clone(), "watch", watch_transport)
+            .with_atom("settings", watch_settings.clone())
+            .start();
+        assert_eq!(*watch.clone().get(&*watch_settings), updated);
+        assert_eq!(*watch.clone().get(&*watch_local), 0);
+
+        // Then every change goes both ways
+        let updated = Settings {
+            name: "watch".into(),
+            count: 2,
+        };
+        watch.set_primitive(&watch_settings, Arc::new(updated.clone()));
+        assert_eq!(*phone.clone().get(&*phone_settings), updated);
+        phone.set_primitive(&phone_settings, Arc::new(settings.clone()));
+        assert_eq!(*watch.clone().get(&*watch_settings), settings);
+    }
+
+    #[test]
+    fn test_sync_last_writer_wins() {
+        let now = Arc::new(AtomicU64::new(100));
+        let clock = {
+            let now = now.clone();
+            move || now.load(Ordering::SeqCst)
+        };
+        let (a_transport, b_transport) = ChannelTransport::pair();
+        let link = a_transport.clone();
+        let (a, b) = (JotaiStore::new(), JotaiStore::new());
+        let (a_count, b_count) = (Arc::new(atom(0)), Arc::new(atom(0)));
+        let _a_sync = StoreSync::new(a.clone(), "a", a_transport)
+            .with_clock(clock.clone())
+            .with_atom("count", a_count.clone())
+            .start();
+        let _b_sync = StoreSync::new(b.clone(), "b", b_transport)
+            .with_clock(clock)
+            .with_atom("count", b_count.clone())
+            .start();
+
+        // Both write while disconnected, the later write wins on both sides
+        link.disconnect();
+        b.set_primitive(&b_count, Arc::new(2));
+        now.store(200, Ordering::SeqCst);
+        a.set_primitive(&a_count, Arc::new(1));
+        link.reconnect();
+        assert_eq!(*a.clone().get(&*a_count), 1);
+        assert_eq!(*b.clone().get(&*b_count), 1);
+
+        // Same timestamp, the greater replica name wins
+        link.disconnect();
+        now.store(300, Ordering::SeqCst);
+        a.set_primitive(&a_count, Arc::new(3));
+        b.set_primitive(&b_count, Arc::new(4));
+        link.reconnect();
+        assert_eq!(*a.clone().get(&*a_count), 4);
+        assert_eq!(*b.clone().get(&*b_count), 4);
+    }
+
+    #[test]
+    fn test_sync_write_before_later_start() {
+        let (phone_transport, watch_transport) = ChannelTransport::pair();
+        let (phone, watch) = (JotaiStore::new(), JotaiStore::new());
+        let (phone_count, watch_count) = (Arc::new(atom(0)), Arc::new(atom(0)));
+        let _phone_sync = StoreSync::new(phone.clone(), "phone", phone_transport)
+            .with_clock(|| 100)
+            .with_atom("count", phone_count.clone())
+            .start();
+
+        // Only the side that starts later wrote, its value wins over the untouched default
+        watch.set_primitive(&watch_count, Arc::new(5));
+        let _watch_sync = StoreSync::new(watch.clone(), "watch", watch_transport)
+            .with_clock(|| 200)
+            .with_atom("count", watch_count.clone())
+            .start();
+        assert_eq!(*phone.clone().get(&*phone_count), 5);
+        assert_eq!(*watch.clone().get(&*watch_count), 5);
+    }
+
+    // Drops everything it's asked to send while `losing` is set
+    struct LossyTransport {
+        inner: ChannelTransport,
+        losing: Arc<AtomicBool>,
+    }
+    impl SyncTransport for LossyTransport {
+        fn send(&self, message: String) {
+            if !self.losing.load(Ordering::SeqCst) {
+                self.inner.send(message);
+            }
+        }
+        fn on_message(&self, handler: Box<dyn Fn(String) + Send + Sync>) {
+            self.inner.on_message(handler);
+        }
+        fn on_connect(&self, handler: Box<dyn Fn() + Send + Sync>) {
+            self.inner.on_connect(handler);
+        }
+    }
+
+    #[test]
+    fn test_sync_resync_on_reconnect() {
+        let (a_transport, b_transport) = ChannelTransport::pair();
+        let link = a_transport.clone();
+        let losing = Arc::new(AtomicBool::new(false));
+        let (a, b) = (JotaiStore::new(), JotaiStore::new());
+        let (a_count, b_count) = (Arc::new(atom(0)), Arc::new(atom(0)));
+        let _a_sync = StoreSync::new(a.clone(), "a", a_transport)
+            .with_atom("count", a_count.clone())
+            .start();
+        let lossy = LossyTransport {
+            inner: b_transport,
+            losing: losing.clone(),
+        };
+        let _b_sync = StoreSync::new(b.clone(), "b", lossy)
+            .with_atom("count", b_count.clone())
+            .start();
+
+        losing.store(true, Ordering::SeqCst);
+        b.set_primitive(&b_count, Arc::new(7));
+        losing.store(false, Ordering::SeqCst);
+        assert_eq!(*a.clone().get(&*a_count), 0);
+
+        // The state exchanged on reconnect carries the lost write
+        link.disconnect();
+        link.reconnect();
+        assert_eq!(*a.clone().get(&*a_count), 7);
+        assert_eq!(*b.clone().get(&*b_count), 7);
+    }
+}
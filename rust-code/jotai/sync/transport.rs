@@ -0,0 +1,114 @@
+use std::sync::{Arc, Mutex};
+
+type Handler = Arc<dyn Fn(String) + Send + Sync>;
+type ConnectHandler = Arc<dyn Fn() + Send + Sync>;
+
+/// Carries sync messages to the other store, e.g. over a watch session or a JS bridge
+pub trait SyncTransport: Send + Sync {
+    fn send(&self, message: String);
+    /// Replaces the handler for messages from the other store
+    fn on_message(&self, handler: Box<dyn Fn(String) + Send + Sync>);
+    /// Replaces the handler called when the connection comes back, transports that may lose
+    /// messages call it so both sides exchange their full state again
+    fn on_connect(&self, _handler: Box<dyn Fn() + Send + Sync>) {}
+}
+
+#[derive(Default)]
+struct Endpoint {
+    handler: Option<Handler>,
+    on_connect: Option<ConnectHandler>,
+    queued: Vec<String>,
+}
+
+struct Link {
+    ends: [Endpoint; 2],
+    connected: bool,
+}
+
+/// One end of an in-process connection. Messages are delivered synchronously, and queued while
+/// the other end has no handler yet or the link is disconnected. Clones are the same end.
+#[derive(Clone)]
+pub struct ChannelTransport {
+    link: Arc<Mutex<Link>>,
+    side: usize,
+}
+impl ChannelTransport {
+    pub fn pair() -> (Self, Self) {
+        let link = Arc::new(Mutex::new(Link {
+            ends: Default::default(),
+            connected: true,
+        }));
+        (
+            Self {
+                link: link.clone(),
+                side: 0,
+            },
+            Self { link, side: 1 },
+        )
+    }
+
+    /// Queues messages in both directions until `reconnect`
+    pub fn disconnect(&self) {
+        self.link.lock().unwrap().connected = false;
+    }
+
+    /// Delivers everything queued while disconnected, then tells both ends it's connected again
+    pub fn reconnect(&self) {
+        let on_connect: Vec<_> = {
+            let mut link = self.link.lock().unwrap();
+            link.connected = true;
+            link.ends
+                .iter()
+                .filter_map(|end| end.on_connect.clone())
+                .collect()
+        };
+        self.flush(0);
+        self.flush(1);
+        on_connect.iter().for_each(|on_connect| on_connect());
+    }
+
+    fn flush(&self, side: usize) {
+        let (handler, queued) = {
+            let mut link = self.link.lock().unwrap();
+            let end = &mut link.ends[side];
+            let Some(handler) = end.handler.clone() else {
+                return;
+            };
+            (handler, std::mem::take(&mut end.queued))
+        };
+        queued.into_iter().for_each(|message| handler(message));
+    }
+}
+impl SyncTransport for ChannelTransport {
+    fn send(&self, message: String) {
+        let handler = {
+            let mut link = self.link.lock().unwrap();
+            let connected = link.connected;
+            let peer = &mut link.ends[1 - self.side];
+            match &peer.handler {
+                Some(handler) if connected => handler.clone(),
+                _ => {
+                    peer.queued.push(message);
+                    return;
+                }
+            }
+        };
+        // Outside the lock, the handler may answer right away
+        handler(message);
+    }
+
+    fn on_message(&self, handler: Box<dyn Fn(String) + Send + Sync>) {
+        let connected = {
+            let mut link = self.link.lock().unwrap();
+            link.ends[self.side].handler = Some(Arc::from(handler));
+            link.connected
+        };
+        if connected {
+            self.flush(self.side);
+        }
+    }
+
+    fn on_connect(&self, handler: Box<dyn Fn() + Send + Sync>) {
+        self.link.lock().unwrap().ends[self.side].on_connect = Some(Arc::from(handler));
+    }
+}
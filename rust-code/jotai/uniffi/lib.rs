@@ -0,0 +1,209 @@
+use jotai::JotaiStore;
+use std::sync::Arc;
+
+uniffi::setup_scaffolding!();
+
+/// Store handle shared by every uniffi binding, see `uniffi_atom!`
+#[derive(uniffi::Object)]
+pub struct RustJotaiStore(pub Arc<JotaiStore>);
+
+#[uniffi::export]
+pub fn create_store() -> RustJotaiStore {
+    RustJotaiStore(JotaiStore::new())
+}
+
+#[uniffi::export]
+impl RustJotaiStore {
+    /// Recomputes every atom that failed, see `JotaiStore::retry`
+    pub fn retry(&self) {
+        self.0.retry();
+    }
+}
+
+#[uniffi::export(callback_interface)]
+pub trait ClosureCallback: Send + Sync {
+    // notify is a reserved word in kotlin 🤦
+    fn notif(&self);
+}
+
+#[derive(uniffi::Object)]
+pub struct Cleanup {
+    callback: Box<dyn Fn() + 'static + Send + Sync>,
+}
+impl Cleanup {
+    pub fn new(callback: impl Fn() + 'static + Send + Sync) -> Self {
+        Self {
+            callback: Box::new(callback),
+        }
+    }
+}
+#[uniffi::export]
+impl Cleanup {
+    fn dispose(&self) {
+        (self.callback)();
+    }
+}
+
+// Lets closures passed to `uniffi_atom!` infer their argument type
+#[doc(hidden)]
+pub fn map_value<T, R>(value: &T, f: impl Fn(&T) -> R) -> R {
+    f(value)
+}
+
+/// Generates a uniffi Object for a static atom, taking a `RustJotaiStore` in its constructor.
+///
+/// - `get: Type` adds `get()` and `sub(callback)`. The callback interface is generated with an
+///   `on_change(value: Type)` method. An optional `=> |value| ...` converts the atom's value.
+/// - `set: Type` adds `set(value)`, `set: ()` adds `set()`, for any atom implementing
+///   `WriteAtom`. An optional `=> |value| ...` converts the argument.
+///
+/// ```ignore
+/// uniffi_atom!(LogAtom {
+///     atom: LOG_ATOM,
+///     get: Vec<Log> => |logs| logs.iter().cloned().map(Log::from).collect(),
+///     callback: LogAtomCallback,
+/// });
+/// uniffi_atom!(DeleteLogsAtom {
+///     atom: DELETE_LOGS_ATOM,
+///     set: (),
+/// });
+/// ```
+#[macro_export]
+macro_rules! uniffi_atom {
+    ($name:ident {
+        atom: $atom:expr,
+        get: $ty:ty $(=> $map:expr)?,
+        callback: $callback:ident $(,)?
+    }) => {
+        $crate::uniffi_atom!(@object $name);
+        #[uniffi::export]
+        impl $name {
+            pub fn get(&self) -> $ty {
+                let atom = $atom.clone();
+                let value = self.store.0.clone().get(&*atom);
+                $crate::uniffi_atom!(@map *value, $ty $(, $map)?)
+            }
+            pub fn sub(&self, callback: Box<dyn $callback>) -> ::std::sync::Arc<$crate::Cleanup> {
+                let atom = $atom.clone();
+                let dispose = self.store.0.clone().sub_value(atom, move |value, _| {
+                    callback.on_change($crate::uniffi_atom!(@map *value, $ty $(, $map)?));
+                });
+                ::std::sync::Arc::new($crate::Cleanup::new(dispose))
+            }
+        }
+        #[uniffi::export(callback_interface)]
+        pub trait $callback: Send + Sync {
+            fn on_change(&self, value: $ty);
+        }
+    };
+    ($name:ident {
+        atom: $atom:expr,
+        set: () $(,)?
+    }) => {
+        $crate::uniffi_atom!(@object $name);
+        #[uniffi::export]
+        impl $name {
+            pub fn set(&self) {
+                let atom = $atom.clone();
+                self.store.0.clone().set(&*atom, ::std::sync::Arc::new(()));
+            }
+        }
+    };
+    ($name:ident {
+        atom: $atom:expr,
+        set: $ty:ty $(=> $map:expr)? $(,)?
+    }) => {
+        $crate::uniffi_atom!(@object $name);
+        #[uniffi::export]
+        impl $name {
+            pub fn set(&self, value: $ty) {
+                let atom = $atom.clone();
+                let arg = $crate::uniffi_atom!(@map value, $ty $(, $map)?);
+                self.store.0.clone().set(&*atom, ::std::sync::Arc::new(arg));
+            }
+        }
+    };
+    (@object $name:ident) => {
+        #[derive(uniffi::Object)]
+        pub struct $name {
+            store: ::std::sync::Arc<$crate::RustJotaiStore>,
+        }
+        #[uniffi::export]
+        impl $name {
+            #[uniffi::constructor]
+            pub fn new(store: ::std::sync::Arc<$crate::RustJotaiStore>) -> ::std::sync::Arc<Self> {
+                ::std::sync::Arc::new(Self { store })
+            }
+        }
+    };
+    (@map $value:expr, $ty:ty) => {
+        $crate::map_value(&$value, |value: &$ty| value.clone())
+    };
+    (@map $value:expr, $ty:ty, $map:expr) => {
+        $crate::map_value(&$value, $map)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use jotai::{atom, dispatch_atom, DispatchAtom, PrimitiveAtom};
+    use std::sync::{Arc, LazyLock, Mutex};
+
+    use super::*;
+
+    static COUNTER_ATOM: LazyLock<Arc<PrimitiveAtom<i32>>> = LazyLock::new(|| Arc::new(atom(1)));
+    static RESET_ATOM: LazyLock<Arc<DispatchAtom<()>>> = LazyLock::new(|| {
+        Arc::new(dispatch_atom(|setter, _| {
+            setter.set_primitive(&COUNTER_ATOM, Arc::new(0))
+        }))
+    });
+
+    uniffi_atom!(CounterAtom {
+        atom: COUNTER_ATOM,
+        get: i32,
+        callback: CounterCallback,
+    });
+    uniffi_atom!(CounterLabelAtom {
+        atom: COUNTER_ATOM,
+        get: String => |count| format!("count: {count}"),
+        callback: CounterLabelCallback,
+    });
+    uniffi_atom!(SetCounterAtom {
+        atom: COUNTER_ATOM,
+        set: String => |value| value.parse().unwrap_or(0),
+    });
+    uniffi_atom!(ResetCounterAtom {
+        atom: RESET_ATOM,
+        set: (),
+    });
+
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+    impl CounterLabelCallback for Recorder {
+        fn on_change(&self, value: String) {
+            self.0.lock().unwrap().push(value);
+        }
+    }
+
+    #[test]
+    fn test_uniffi_atom() {
+        let store = Arc::new(create_store());
+        let counter = CounterAtom::new(store.clone());
+        let label = CounterLabelAtom::new(store.clone());
+        let set_counter = SetCounterAtom::new(store.clone());
+        let reset = ResetCounterAtom::new(store.clone());
+        let values = Arc::new(Mutex::new(vec![]));
+        let cleanup = label.sub(Box::new(Recorder(values.clone())));
+
+        set_counter.set("5".to_string());
+        assert_eq!(counter.get(), 5);
+        assert_eq!(label.get(), "count: 5");
+        reset.set();
+        assert_eq!(counter.get(), 0);
+        cleanup.dispose();
+        set_counter.set("7".to_string());
+        assert_eq!(
+            *values.lock().unwrap(),
+            vec!["count: 5".to_string(), "count: 0".to_string()]
+        );
+    }
+}
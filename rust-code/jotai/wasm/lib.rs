@@ -0,0 +1,169 @@
+use jotai::JotaiStore;
+use js_sys::Function;
+use send_wrapper::SendWrapper;
+use serde::Serialize;
+use serde_wasm_bindgen::to_value;
+use std::sync::Arc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+#[doc(hidden)]
+pub use {jotai, js_sys};
+
+// The store only accepts `Send + Sync` callbacks, so JS functions are wrapped in `SendWrapper`.
+// That holds because wasm32 runs on a single thread, a threaded build would panic on first use.
+#[cfg(all(target_arch = "wasm32", target_feature = "atomics"))]
+compile_error!("jotai-wasm requires a single-threaded wasm32 target");
+
+#[wasm_bindgen(typescript_custom_section)]
+const TS_APPEND: &'static str = r#"
+export type Dispose = () => void;"#;
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(typescript_type = "Dispose")]
+    pub type DisposeType;
+}
+
+/// Store handle shared by every wasm binding, see `wasm_atom!`
+#[wasm_bindgen(js_name = "JotaiStore")]
+pub struct JsJotaiStore(#[wasm_bindgen(skip)] pub Arc<JotaiStore>);
+
+#[wasm_bindgen(js_class = "JotaiStore")]
+impl JsJotaiStore {
+    /// Recomputes every atom that failed, see `JotaiStore::retry`
+    pub fn retry(&self) {
+        self.0.retry();
+    }
+}
+
+#[wasm_bindgen(js_name = "createStore")]
+pub fn create_store() -> JsJotaiStore {
+    JsJotaiStore(JotaiStore::new())
+}
+
+#[doc(hidden)]
+pub fn listener<T: Serialize>(callback: Function) -> impl Fn(T) + Send + Sync + 'static {
+    let callback = SendWrapper::new(callback);
+    move |value| {
+        let value = to_value(&value).unwrap_throw();
+        // A throwing callback can't unwind through the store, so the exception is dropped
+        let _ = callback.call1(&JsValue::NULL, &value);
+    }
+}
+
+#[doc(hidden)]
+pub fn dispose_fn(dispose: impl Fn() + 'static) -> DisposeType {
+    Closure::<dyn Fn()>::new(dispose)
+        .into_js_value()
+        .unchecked_into()
+}
+
+// Lets closures passed to `wasm_atom!` infer their argument type
+#[doc(hidden)]
+pub fn map_value<T, R>(value: &T, f: impl Fn(&T) -> R) -> R {
+    f(value)
+}
+
+/// Generates a wasm-bindgen class for a static atom, taking a `JotaiStore` in its constructor.
+///
+/// - `get: Type` adds `get()` and `sub(callback)`, which returns a `Dispose` function. `Type` is
+///   returned to JS through its wasm ABI and passed to callbacks with `serde_wasm_bindgen`, so
+///   non-primitive values should derive `Tsify` and `Serialize`. An optional `=> |value| ...`
+///   converts the atom's value.
+/// - `set: Type` adds `set(value)`, `set: ()` adds `set()`, for any atom implementing
+///   `WriteAtom`. An optional `=> |value| ...` converts the argument.
+///
+/// ```ignore
+/// wasm_atom!(CounterAtom {
+///     atom: COUNTER_ATOM,
+///     get: Counter => |count| Counter { count: *count },
+/// });
+/// wasm_atom!(SetCounterAtom {
+///     atom: COUNTER_ATOM,
+///     set: i32,
+/// });
+/// ```
+#[macro_export]
+macro_rules! wasm_atom {
+    ($name:ident {
+        atom: $atom:expr,
+        get: $ty:ty $(=> $map:expr)? $(,)?
+    }) => {
+        $crate::wasm_atom!(@object $name);
+        #[::wasm_bindgen::prelude::wasm_bindgen]
+        impl $name {
+            pub fn get(&self) -> $ty {
+                let atom = $atom.clone();
+                let value = self.store.clone().get(&*atom);
+                $crate::wasm_atom!(@map *value, $ty $(, $map)?)
+            }
+            // Typed in the custom section below, from the return type of `get`
+            #[wasm_bindgen(skip_typescript)]
+            pub fn sub(&self, callback: $crate::js_sys::Function) -> $crate::DisposeType {
+                let atom = $atom.clone();
+                let listener = $crate::listener::<$ty>(callback);
+                let dispose = self.store.clone().sub_value(atom, move |value, _| {
+                    listener($crate::wasm_atom!(@map *value, $ty $(, $map)?));
+                });
+                $crate::dispose_fn(dispose)
+            }
+        }
+        #[::wasm_bindgen::prelude::wasm_bindgen(typescript_custom_section)]
+        const _: &str = concat!(
+            "\nexport interface ",
+            stringify!($name),
+            " {\n  sub(callback: (value: ReturnType<",
+            stringify!($name),
+            "[\"get\"]>) => void): Dispose;\n}"
+        );
+    };
+    ($name:ident {
+        atom: $atom:expr,
+        set: () $(,)?
+    }) => {
+        $crate::wasm_atom!(@object $name);
+        #[::wasm_bindgen::prelude::wasm_bindgen]
+        impl $name {
+            pub fn set(&self) {
+                let atom = $atom.clone();
+                self.store.clone().set(&*atom, ::std::sync::Arc::new(()));
+            }
+        }
+    };
+    ($name:ident {
+        atom: $atom:expr,
+        set: $ty:ty $(=> $map:expr)? $(,)?
+    }) => {
+        $crate::wasm_atom!(@object $name);
+        #[::wasm_bindgen::prelude::wasm_bindgen]
+        impl $name {
+            pub fn set(&self, value: $ty) {
+                let atom = $atom.clone();
+                let arg = $crate::wasm_atom!(@map value, $ty $(, $map)?);
+                self.store.clone().set(&*atom, ::std::sync::Arc::new(arg));
+            }
+        }
+    };
+    (@object $name:ident) => {
+        #[::wasm_bindgen::prelude::wasm_bindgen]
+        pub struct $name {
+            store: ::std::sync::Arc<$crate::jotai::JotaiStore>,
+        }
+        #[::wasm_bindgen::prelude::wasm_bindgen]
+        impl $name {
+            #[wasm_bindgen(constructor)]
+            pub fn new(store: &$crate::JsJotaiStore) -> Self {
+                Self {
+                    store: store.0.clone(),
+                }
+            }
+        }
+    };
+    (@map $value:expr, $ty:ty) => {
+        $crate::map_value(&$value, |value: &$ty| value.clone())
+    };
+    (@map $value:expr, $ty:ty, $map:expr) => {
+        $crate::map_value(&$value, $map)
+    };
+}
@@ -0,0 +1,64 @@
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use futures::channel::mpsc::UnboundedReceiver;
+use futures::Stream;
+
+/// Stream of an atom's values, the subscription is disposed when it's dropped
+pub struct AtomStream<T> {
+    pub(crate) receiver: UnboundedReceiver<Arc<T>>,
+    pub(crate) dispose: Box<dyn Fn() + Send + Sync>,
+}
+impl<T> Stream for AtomStream<T> {
+    type Item = Arc<T>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.receiver).poll_next(cx)
+    }
+}
+impl<T> Drop for AtomStream<T> {
+    fn drop(&mut self) {
+        (self.dispose)();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+    use futures::StreamExt;
+
+    use crate::*;
+
+    #[test]
+    fn test_watch_atom() {
+        let store = JotaiStore::new();
+        let counter_atom = Arc::new(atom(1));
+        let double_atom = Arc::new(select_atom({
+            let counter_atom = counter_atom.clone();
+            move |getter| *getter.get(counter_atom.clone()) * 2
+        }));
+        let mut stream = store.clone().watch(double_atom.clone());
+        store.clone().set_primitive(&counter_atom, Arc::new(2));
+        store.clone().set_primitive(&counter_atom, Arc::new(3));
+        assert_eq!(block_on(stream.next()), Some(Arc::new(2)));
+        assert_eq!(block_on(stream.next()), Some(Arc::new(4)));
+        assert_eq!(block_on(stream.next()), Some(Arc::new(6)));
+
+        let values = std::thread::scope(|scope| {
+            let handle = scope.spawn(|| block_on(stream.by_ref().take(2).collect::<Vec<_>>()));
+            store.clone().set_primitive(&counter_atom, Arc::new(4));
+            store.clone().set_primitive(&counter_atom, Arc::new(5));
+            handle.join().unwrap()
+        });
+        assert_eq!(values, vec![Arc::new(8), Arc::new(10)]);
+
+        drop(stream);
+        let double = store
+            .inspect()
+            .into_iter()
+            .find(|a| a.id == *double_atom.get_id())
+            .unwrap();
+        assert_eq!(double.subscribers, 0);
+    }
+}
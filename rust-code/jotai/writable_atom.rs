@@ -0,0 +1,142 @@
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+use crate::atom_base::*;
+use crate::getter_setter::{Getter, Setter};
+
+type Write<Arg> = Box<dyn Fn(&mut Setter, Arc<Arg>) + Send + Sync>;
+
+pub struct WritableAtom<T, Arg> {
+    id: Arc<AtomId>,
+    read: Box<dyn Fn(&mut Getter) -> T + Send + Sync>,
+    write: Write<Arg>,
+    equals: Equals<T>,
+}
+impl<T, Arg> PartialEq for WritableAtom<T, Arg> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+impl<T, Arg> Eq for WritableAtom<T, Arg> {}
+impl<T, Arg> Hash for WritableAtom<T, Arg> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+impl<T: PartialEq + 'static, Arg: 'static> WritableAtom<T, Arg> {
+    pub fn new<R, W>(read: R, write: W) -> Self
+    where
+        R: Fn(&mut Getter) -> T + 'static + Send + Sync,
+        W: Fn(&mut Setter, Arc<Arg>) + 'static + Send + Sync,
+    {
+        Self::new_with_equals(read, write, default_equals())
+    }
+}
+impl<T: 'static, Arg: 'static> WritableAtom<T, Arg> {
+    /// For values without `PartialEq`, or where comparing them is too expensive
+    pub fn new_with_equals<R, W>(read: R, write: W, equals: Equals<T>) -> Self
+    where
+        R: Fn(&mut Getter) -> T + 'static + Send + Sync,
+        W: Fn(&mut Setter, Arc<Arg>) + 'static + Send + Sync,
+    {
+        Self {
+            id: Arc::new(AtomId::new()),
+            read: Box::new(read),
+            write: Box::new(write),
+            equals,
+        }
+    }
+
+    /// Replaces the equality used to decide whether a recomputed value notifies dependents
+    pub fn with_equals<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&T, &T) -> bool + 'static + Send + Sync,
+    {
+        self.equals = Arc::new(f);
+        self
+    }
+}
+impl<T, Arg> Atom for WritableAtom<T, Arg> {
+    fn get_id(&self) -> Arc<AtomId> {
+        self.id.clone()
+    }
+}
+impl<T, Arg> ReadAtom<T> for WritableAtom<T, Arg> {
+    fn get_read(&self) -> &Box<dyn Fn(&mut Getter) -> T + Send + Sync> {
+        &self.read
+    }
+    fn is_equal(&self, a: &T, b: &T) -> bool {
+        (self.equals)(a, b)
+    }
+}
+impl<T, Arg> WriteAtom<Arg> for WritableAtom<T, Arg> {
+    fn write(&self, setter: &mut Setter, arg: Arc<Arg>) {
+        (self.write)(setter, arg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use crate::*;
+
+    #[test]
+    fn test_writable_atom() {
+        let store = JotaiStore::new();
+        let celsius_atom = Arc::new(atom(25.0));
+        let fahrenheit_atom = Arc::new(writable_atom(
+            {
+                let celsius_atom = celsius_atom.clone();
+                move |getter| *getter.get(celsius_atom.clone()) * 9.0 / 5.0 + 32.0
+            },
+            {
+                let celsius_atom = celsius_atom.clone();
+                move |setter, fahrenheit: Arc<f64>| {
+                    setter.set_primitive(&celsius_atom, Arc::new((*fahrenheit - 32.0) * 5.0 / 9.0));
+                }
+            },
+        ));
+        let sub_counter = Arc::new(Mutex::new(0));
+        let _dispose = store.clone().sub(fahrenheit_atom.clone(), {
+            let counter = sub_counter.clone();
+            move |_| *counter.lock().unwrap() += 1
+        });
+        assert_eq!(*store.clone().get(&*fahrenheit_atom), 77.0);
+
+        store.clone().set(&*fahrenheit_atom, Arc::new(212.0));
+        assert_eq!(*store.clone().get(&*celsius_atom), 100.0);
+        assert_eq!(*store.clone().get(&*fahrenheit_atom), 212.0);
+        assert_eq!(*sub_counter.lock().unwrap(), 1);
+
+        store.clone().set_primitive(&celsius_atom, Arc::new(0.0));
+        assert_eq!(*store.clone().get(&*fahrenheit_atom), 32.0);
+        assert_eq!(*sub_counter.lock().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_writable_atom_from_setter() {
+        let store = JotaiStore::new();
+        let counter_atom = Arc::new(atom(1));
+        let double_atom = Arc::new(writable_atom(
+            {
+                let counter_atom = counter_atom.clone();
+                move |getter| *getter.get(counter_atom.clone()) * 2
+            },
+            {
+                let counter_atom = counter_atom.clone();
+                move |setter, value: Arc<i32>| {
+                    setter.set_primitive(&counter_atom, Arc::new(*value / 2));
+                }
+            },
+        ));
+        let reset_atom = dispatch_atom({
+            let double_atom = double_atom.clone();
+            move |setter, _: Arc<()>| setter.set(&*double_atom, Arc::new(0))
+        });
+        store.clone().set(&*double_atom, Arc::new(8));
+        assert_eq!(*store.clone().get(&*counter_atom), 4);
+        store.clone().set(&reset_atom, Arc::new(()));
+        assert_eq!(*store.clone().get(&*double_atom), 0);
+    }
+}
@@ -3,7 +3,7 @@ load("@rules_android//android:rules.bzl", "android_library")
 load("@rules_kotlin//kotlin:android.bzl", "kt_android_library")
 load("@rules_rust//rust:defs.bzl", "rust_library", "rust_shared_library")
 
-def rust_uniffi_bindgen(name, srcs, deps = [], module_name = None, extra_module_names = [], proc_macro_deps = [], **kwargs):
+def rust_uniffi_bindgen(name, srcs, deps = [], module_name = None, extra_module_names = [], swift_deps = [], proc_macro_deps = [], **kwargs):
     module_name = module_name or name
     under_module_name = module_name.replace("-", "_")
 
@@ -98,7 +98,8 @@ def rust_uniffi_bindgen(name, srcs, deps = [], module_name = None, extra_module_
         name = name + "-swift",
         srcs = [":%s.swift" % under_module_name],
         module_name = under_module_name,
-        deps = [":%s-objc" % name],
+        # Swift modules of other uniffi libraries whose types this one uses
+        deps = [":%s-objc" % name] + swift_deps,
     )
 
     android_library(